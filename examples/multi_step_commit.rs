@@ -213,7 +213,7 @@ Binary files a/logo.png and b/logo.png differ
     let client = Client::new();
     let model = "gpt-4";
 
-    match generate_commit_message_multi_step(&client, model, example_diff, Some(72)).await {
+    match generate_commit_message_multi_step(&client, model, example_diff, Some(72), "en").await {
       Ok(message) => {
         println!("Generated commit message: {message}\n");
       }
@@ -224,7 +224,7 @@ Binary files a/logo.png and b/logo.png differ
   } else {
     println!("No OPENAI_API_KEY found. Using local analysis...\n");
 
-    match generate_commit_message_local(example_diff, Some(72)) {
+    match generate_commit_message_local(example_diff, Some(72), "en") {
       Ok(message) => {
         println!("Generated commit message: {message}\n");
       }
@@ -283,7 +283,7 @@ Binary files a/logo.png and b/logo.png differ
   // Generate candidates
   println!("\n4. Generating commit message candidates:");
   use ai::multi_step_analysis::generate_commit_messages;
-  let generate_result = generate_commit_messages(score_result.files_with_scores, 72);
+  let generate_result = generate_commit_messages(score_result.files_with_scores, 72, "en");
   for (i, candidate) in generate_result.candidates.iter().enumerate() {
     println!("   Candidate {}: \"{}\"", i + 1, candidate);
   }
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::{mask_command, AppConfig};
+
+/// Where the API key is read from instead of being stored in the configuration
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeySource {
+  /// A shell command that prints the key, e.g. `pass show openai`
+  Command(String),
+  /// A file only its owner can read
+  File(PathBuf),
+  /// An environment variable holding the key
+  Env(String)
+}
+
+/// Keys read during this process, so a command runs at most once per invocation
+static RESOLVED: Mutex<BTreeMap<KeySource, String>> = Mutex::new(BTreeMap::new());
+
+impl KeySource {
+  /// The source configured in `settings`. When more than one is set, the command wins
+  /// over the file and the file over the environment variable.
+  pub fn of(settings: &AppConfig) -> Option<KeySource> {
+    let set = |value: &Option<String>| {
+      value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+    };
+
+    set(&settings.openai_api_key_command)
+      .map(KeySource::Command)
+      .or_else(|| set(&settings.openai_api_key_file).map(|path| KeySource::File(expand_home(&path))))
+      .or_else(|| set(&settings.openai_api_key_env).map(KeySource::Env))
+  }
+
+  /// Reads the key, running the command or opening the file only the first time
+  pub fn resolve(&self) -> Result<String> {
+    let mut resolved = RESOLVED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(key) = resolved.get(self) {
+      return Ok(key.clone());
+    }
+
+    let key = self.read()?;
+    resolved.insert(self.clone(), key.clone());
+    Ok(key)
+  }
+
+  fn read(&self) -> Result<String> {
+    let text = match self {
+      KeySource::Command(command) => {
+        // stderr stays on the terminal so prompts such as gpg's pinentry still show up
+        let output = shell(command)
+          .stdin(Stdio::inherit())
+          .stderr(Stdio::inherit())
+          .output()
+          .with_context(|| format!("Failed to run {self}"))?;
+        if !output.status.success() {
+          bail!("{self} failed ({})", output.status);
+        }
+        String::from_utf8(output.stdout).with_context(|| format!("{self} printed a key that is not UTF-8"))?
+      }
+      KeySource::File(path) => {
+        check_permissions(path)?;
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {self}"))?
+      }
+      KeySource::Env(name) => std::env::var(name).with_context(|| format!("{self} is not set"))?
+    };
+
+    // `pass` and similar tools print the secret on the first line and metadata after it
+    match text.lines().next().map(str::trim) {
+      Some(key) if !key.is_empty() => Ok(key.to_string()),
+      _ => bail!("{self} returned an empty API key")
+    }
+  }
+}
+
+/// Names the source without its secrets: a command shows only its program, since its
+/// arguments may contain the key itself (`echo sk-…`)
+impl fmt::Display for KeySource {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      KeySource::Command(command) => write!(f, "openai-api-key-command `{}`", mask_command(command)),
+      KeySource::File(path) => write!(f, "openai-api-key-file {}", path.display()),
+      KeySource::Env(name) => write!(f, "openai-api-key-env ${name}")
+    }
+  }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+  let mut shell = Command::new("sh");
+  shell.arg("-c").arg(command);
+  shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+  let mut shell = Command::new("cmd");
+  shell.arg("/C").arg(command);
+  shell
+}
+
+/// `~/key` relative to the home directory, anything else as is
+fn expand_home(path: &str) -> PathBuf {
+  match (path.strip_prefix("~/"), home::home_dir()) {
+    (Some(rest), Some(home)) => home.join(rest),
+    _ => PathBuf::from(path)
+  }
+}
+
+/// Refuses key files that the group or other users can access, like ssh does
+#[cfg(unix)]
+fn check_permissions(path: &std::path::Path) -> Result<()> {
+  use std::os::unix::fs::PermissionsExt;
+
+  let mode = std::fs::metadata(path)
+    .with_context(|| format!("Failed to read openai-api-key-file {}", path.display()))?
+    .permissions()
+    .mode();
+  if mode & 0o077 != 0 {
+    bail!(
+      "openai-api-key-file {} is accessible by other users (mode {:o}); run `chmod 600 {}`",
+      path.display(),
+      mode & 0o777,
+      path.display()
+    );
+  }
+  Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &std::path::Path) -> Result<()> {
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_source_precedence_and_first_line() {
+    let settings = AppConfig {
+      openai_api_key_file: Some("/run/secrets/openai".to_string()),
+      openai_api_key_env: Some("WORK_OPENAI_KEY".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(KeySource::of(&settings), Some(KeySource::File(PathBuf::from("/run/secrets/openai"))));
+    assert_eq!(KeySource::of(&AppConfig::default()), None);
+
+    let command = KeySource::Command("printf 'sk-from-command\\nlogin: dev\\n'".to_string());
+    assert_eq!(command.resolve().unwrap(), "sk-from-command");
+    assert!(KeySource::Command("exit 3".to_string())
+      .resolve()
+      .unwrap_err()
+      .to_string()
+      .contains("failed"));
+
+    let inlined = KeySource::Command("echo sk-abcdefghijklmnop".to_string());
+    assert_eq!(inlined.to_string(), "openai-api-key-command `echo …`");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_key_file_must_be_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("openai");
+    std::fs::write(&path, "sk-from-file\n").unwrap();
+
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    let error = KeySource::File(path.clone())
+      .read()
+      .unwrap_err()
+      .to_string();
+    assert!(error.contains("mode 644"), "{error}");
+
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    assert_eq!(KeySource::File(path).read().unwrap(), "sk-from-file");
+  }
+}
@@ -50,7 +50,8 @@ use structopt::StructOpt;
 use indicatif::{ProgressBar, ProgressStyle};
 use anyhow::{bail, Context, Result};
 use git2::{Oid, Repository};
-use ai::{commit, config, debug_output};
+use ai::{commit, config, debug_output, picker};
+use ai::picker::Choice;
 use ai::hook::*;
 
 #[derive(Debug, PartialEq)]
@@ -86,6 +87,19 @@ struct Args {
   sha1:            Option<String>
 }
 
+fn spinner(message: &'static str) -> Result<ProgressBar> {
+  let pb = ProgressBar::new_spinner();
+  let style = ProgressStyle::default_spinner()
+    .tick_strings(&["-", "\\", "|", "/"])
+    .template("{spinner:.blue} {msg}")
+    .context("Failed to create progress bar style")?;
+
+  pb.set_style(style);
+  pb.set_message(message);
+  pb.enable_steady_tick(Duration::from_millis(150));
+  Ok(pb)
+}
+
 impl Args {
   async fn execute(&self) -> Result<()> {
     use Source::*;
@@ -99,11 +113,7 @@ impl Args {
           .clone()
           .unwrap_or("gpt-4.1-mini".to_string())
           .into();
-        let used_tokens = commit::token_used(&model)?;
-        let max_tokens = config::APP_CONFIG
-          .max_tokens
-          .unwrap_or(model.context_size());
-        let remaining_tokens = max_tokens.saturating_sub(used_tokens).max(512); // Ensure minimum 512 tokens
+        let remaining_tokens = commit::remaining_tokens(&model)?;
 
         let tree = match self.sha1.as_deref() {
           Some("HEAD") | None => repo.head().ok().and_then(|head| head.peel_to_tree().ok()),
@@ -130,15 +140,7 @@ impl Args {
           bail!("No changes to commit");
         }
 
-        let pb = ProgressBar::new_spinner();
-        let style = ProgressStyle::default_spinner()
-          .tick_strings(&["-", "\\", "|", "/"])
-          .template("{spinner:.blue} {msg}")
-          .context("Failed to create progress bar style")?;
-
-        pb.set_style(style);
-        pb.set_message("Generating commit message...");
-        pb.enable_steady_tick(Duration::from_millis(150));
+        let pb = spinner("Generating commit message...")?;
 
         // Check if a commit message already exists and is not empty
         if !std::fs::read_to_string(&self.commit_msg_file)?
@@ -154,11 +156,28 @@ impl Args {
           .to_patch(tree, remaining_tokens, model.clone())
           .context("Failed to get patch")?;
 
-        let response = commit::generate(patch.to_string(), remaining_tokens, model, None).await?;
-        std::fs::write(&self.commit_msg_file, response.response.trim())?;
-
+        let mut response = commit::generate_response(patch.to_string(), remaining_tokens, model.clone(), None).await?;
         pb.finish_and_clear();
 
+        // Let the user choose between the candidates on a terminal; IDE commits keep the
+        // message the pipeline selected
+        let message = if picker::is_interactive() {
+          loop {
+            match picker::pick(&response)? {
+              Choice::Accept(message) => break message,
+              Choice::Regenerate => {
+                let pb = spinner("Regenerating commit message...")?;
+                response = commit::generate_response(patch.to_string(), remaining_tokens, model.clone(), None).await?;
+                pb.finish_and_clear();
+              }
+            }
+          }
+        } else {
+          response.message
+        };
+
+        std::fs::write(&self.commit_msg_file, message.trim())?;
+
         Ok(())
       }
     }
@@ -0,0 +1,198 @@
+use anyhow::{bail, Context, Result};
+use git2::{Branch, BranchType, Repository};
+
+use crate::{commit, picker};
+use crate::config::AppConfig;
+use crate::hook::{PatchDiff, PatchRepository};
+use crate::model::Model;
+use crate::multi_step_analysis::FileWithScore;
+use crate::multi_step_integration::score_files_local;
+
+/// Placeholders a branch pattern may use
+const PLACEHOLDERS: [&str; 3] = ["<type>", "<slug>", "<ticket>"];
+
+/// Leading verbs that carry no information once the change type is in the name
+const LEADING_VERBS: &[&str] = &[
+  "add", "adds", "fix", "fixes", "update", "updates", "remove", "removes", "refactor", "implement", "introduce", "improve", "support",
+  "allow", "document", "test", "rename", "move", "extract", "simplify", "create", "resolve", "correct"
+];
+
+/// The most words a slug keeps, so names stay short enough to type
+const MAX_SLUG_WORDS: usize = 5;
+
+/// Checks that `pattern` contains `<slug>` and no placeholders besides `<type>`,
+/// `<slug>` and `<ticket>`.
+pub fn validate_pattern(pattern: &str) -> Result<()> {
+  if !pattern.contains("<slug>") {
+    bail!("branch-pattern must contain <slug>, e.g. {}", crate::config::DEFAULT_BRANCH_PATTERN);
+  }
+
+  let mut rest = pattern;
+  while let Some(start) = rest.find('<') {
+    let end = rest[start..]
+      .find('>')
+      .with_context(|| format!("Unclosed placeholder in branch-pattern '{pattern}'"))?;
+    let placeholder = &rest[start..start + end + 1];
+    if !PLACEHOLDERS.contains(&placeholder) {
+      bail!(
+        "Unknown placeholder {placeholder} in branch-pattern '{pattern}'; use {}",
+        PLACEHOLDERS.join(", ")
+      );
+    }
+    rest = &rest[start + end + 1..];
+  }
+
+  Ok(())
+}
+
+/// Turns a commit subject into a branch slug: lowercase words joined by dashes, without
+/// the leading verb, e.g. `Add config validation` -> `config-validation`.
+pub fn slugify(subject: &str) -> String {
+  let words: Vec<String> = subject
+    .to_lowercase()
+    .split(|c: char| !c.is_ascii_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .map(str::to_string)
+    .collect();
+
+  let skip = usize::from(words.len() > 1 && LEADING_VERBS.contains(&words[0].as_str()));
+  words
+    .into_iter()
+    .skip(skip)
+    .take(MAX_SLUG_WORDS)
+    .collect::<Vec<_>>()
+    .join("-")
+}
+
+/// Classifies a change for the `<type>` placeholder from its subject and files
+pub fn change_type(subject: &str, files: &[FileWithScore]) -> &'static str {
+  if !files.is_empty() && files.iter().all(|file| file.file_category == "docs") {
+    return "docs";
+  }
+  if !files.is_empty() && files.iter().all(|file| file.file_category == "test") {
+    return "test";
+  }
+
+  let verb = subject
+    .split_whitespace()
+    .next()
+    .unwrap_or_default()
+    .to_lowercase();
+  match verb.as_str() {
+    "fix" | "fixes" | "resolve" | "correct" | "prevent" | "handle" => "fix",
+    "add" | "adds" | "implement" | "introduce" | "support" | "allow" | "create" | "enable" => "feat",
+    "refactor" | "rename" | "move" | "extract" | "simplify" | "restructure" => "refactor",
+    _ => "chore"
+  }
+}
+
+fn require_ticket(pattern: &str, ticket: Option<&str>) -> Result<()> {
+  if pattern.contains("<ticket>") && ticket.is_none() {
+    bail!("The branch pattern '{pattern}' uses <ticket>; pass one with --ticket");
+  }
+  Ok(())
+}
+
+/// Fills in the pattern and checks that git accepts the result as a branch name
+pub fn render(pattern: &str, kind: &str, slug: &str, ticket: Option<&str>) -> Result<String> {
+  require_ticket(pattern, ticket)?;
+
+  let name = pattern
+    .replace("<type>", kind)
+    .replace("<slug>", slug)
+    .replace("<ticket>", ticket.unwrap_or_default());
+  if !Branch::name_is_valid(&name)? {
+    bail!("'{name}' is not a valid branch name");
+  }
+
+  Ok(name)
+}
+
+/// Suggests up to `count` branch names for the uncommitted changes (staged, unstaged
+/// and untracked), one per generated message candidate. Names of existing local
+/// branches are skipped.
+pub async fn suggest(
+  repo: &Repository, pattern: &str, ticket: Option<&str>, count: usize, model: Model, settings: Option<&AppConfig>
+) -> Result<Vec<String>> {
+  validate_pattern(pattern)?;
+  require_ticket(pattern, ticket)?;
+
+  let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+  let diff = repo.to_diff(head)?;
+  if diff.is_empty()? {
+    bail!("No changes to name a branch after");
+  }
+
+  let files = score_files_local(&diff.to_full_patch()?)?;
+  let remaining_tokens = commit::remaining_tokens(&model)?;
+  let patch = diff.to_patch(remaining_tokens, model.clone())?;
+  let response = commit::generate_response(patch, remaining_tokens, model, settings).await?;
+
+  let mut names = Vec::new();
+  for subject in picker::options(&response) {
+    let slug = slugify(&subject);
+    if slug.is_empty() {
+      continue;
+    }
+
+    let name = render(pattern, change_type(&subject, &files), &slug, ticket)?;
+    if !names.contains(&name) && repo.find_branch(&name, BranchType::Local).is_err() {
+      names.push(name);
+    }
+    if names.len() == count {
+      break;
+    }
+  }
+
+  if names.is_empty() {
+    bail!("Could not derive a branch name from the changes");
+  }
+
+  Ok(names)
+}
+
+/// Creates `name` at the current commit and points `HEAD` at it. The index and working
+/// tree are left as they are, so uncommitted changes carry over to the new branch.
+pub fn create_and_switch(repo: &Repository, name: &str) -> Result<()> {
+  let reference = format!("refs/heads/{name}");
+  // Without commits there is nothing to branch from; the branch is born on the first commit
+  if let Some(commit) = repo.head().ok().and_then(|head| head.peel_to_commit().ok()) {
+    repo
+      .branch(name, &commit, false)
+      .with_context(|| format!("Failed to create branch {name}"))?;
+  }
+
+  repo
+    .set_head(&reference)
+    .with_context(|| format!("Failed to switch to {name}"))?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_slugify_drops_leading_verb_and_punctuation() {
+    assert_eq!(slugify("Add config validation"), "config-validation");
+    assert_eq!(slugify("Fix crash when `max_tokens` is 0!"), "crash-when-max-tokens-is");
+    assert_eq!(slugify("Update"), "update");
+  }
+
+  #[test]
+  fn test_render_applies_pattern() {
+    assert_eq!(
+      render("<type>/<slug>", "feat", "config-validation", None).unwrap(),
+      "feat/config-validation"
+    );
+    assert_eq!(render("<ticket>-<slug>", "fix", "crash", Some("ABC-123")).unwrap(), "ABC-123-crash");
+    assert!(render("<ticket>-<slug>", "fix", "crash", None).is_err());
+  }
+
+  #[test]
+  fn test_validate_pattern() {
+    assert!(validate_pattern("users/me/<type>/<slug>").is_ok());
+    assert!(validate_pattern("<type>/").is_err());
+    assert!(validate_pattern("<kind>/<slug>").is_err());
+  }
+}
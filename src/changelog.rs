@@ -0,0 +1,453 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use git2::{Commit, Repository};
+
+use crate::hook::{PatchDiff, PatchRepository, Utf8String};
+use crate::multi_step_analysis::FileWithScore;
+use crate::multi_step_integration::score_files_local;
+use crate::pr::{range_commits, resolve_range};
+
+/// Header written when `--prepend` has to create the changelog from scratch
+const CHANGELOG_HEADER: &str = "# Changelog
+
+All notable changes to this project are documented in this file.
+
+The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/),
+and this project adheres to [Semantic Versioning](https://semver.org/spec/v2.0.0.html).
+";
+
+/// A Keep a Changelog section, declared in the order the sections are rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Section {
+  Added,
+  Changed,
+  Deprecated,
+  Removed,
+  Fixed,
+  Security
+}
+
+impl Section {
+  pub const ALL: [Section; 6] =
+    [Section::Added, Section::Changed, Section::Deprecated, Section::Removed, Section::Fixed, Section::Security];
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Section::Added => "Added",
+      Section::Changed => "Changed",
+      Section::Deprecated => "Deprecated",
+      Section::Removed => "Removed",
+      Section::Fixed => "Fixed",
+      Section::Security => "Security"
+    }
+  }
+}
+
+impl fmt::Display for Section {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.as_str())
+  }
+}
+
+/// One changelog line, possibly merged from several commits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+  pub section: Section,
+  /// Conventional commit scope, e.g. `hook` in `fix(hook): ...`
+  pub scope:   Option<String>,
+  pub text:    String
+}
+
+/// A commit subject split into its conventional commit parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Subject {
+  pub(crate) kind:        Option<String>,
+  pub(crate) scope:       Option<String>,
+  pub(crate) breaking:    bool,
+  pub(crate) description: String
+}
+
+pub(crate) fn parse_subject(subject: &str) -> Subject {
+  // Drop a leading ticket tag such as `[ABC-123]`, it says nothing about the change
+  let subject = subject.trim();
+  let subject = match subject
+    .strip_prefix('[')
+    .and_then(|rest| rest.split_once(']'))
+  {
+    Some((_, rest)) => rest.trim_start(),
+    None => subject
+  };
+  if let Some((prefix, description)) = subject.split_once(": ") {
+    let breaking = prefix.ends_with('!');
+    let prefix = prefix.trim_end_matches('!');
+    let (kind, scope) = match prefix.split_once('(') {
+      Some((kind, scope)) => (kind, scope.strip_suffix(')').map(str::to_string)),
+      None => (prefix, None)
+    };
+
+    if !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphabetic()) {
+      return Subject {
+        kind: Some(kind.to_lowercase()),
+        scope,
+        breaking,
+        description: description.trim().to_string()
+      };
+    }
+  }
+
+  Subject {
+    kind:        None,
+    scope:       None,
+    breaking:    false,
+    description: subject.to_string()
+  }
+}
+
+fn contains_any(text: &str, needles: &[&str]) -> bool {
+  needles.iter().any(|needle| text.contains(needle))
+}
+
+/// Classifies a commit from its subject and the files its diff touches.
+///
+/// Security wording wins over everything else, then the conventional commit type,
+/// then the leading verb. When the message says nothing useful the diff decides:
+/// only new files means Added, only deleted files means Removed, anything else Changed.
+pub fn classify(subject: &str, files: &[FileWithScore]) -> Section {
+  let parsed = parse_subject(subject);
+  let text = parsed.description.to_lowercase();
+
+  if parsed.kind.as_deref() == Some("security") || contains_any(&text, &["security", "vulnerab", "cve-", "xss", "injection"]) {
+    return Section::Security;
+  }
+
+  match parsed.kind.as_deref() {
+    Some("feat") => return Section::Added,
+    Some("fix") => return Section::Fixed,
+    Some("revert") => return Section::Removed,
+    _ => {}
+  }
+
+  let verb = text.split_whitespace().next().unwrap_or_default();
+  match verb {
+    "add" | "adds" | "added" | "introduce" | "introduces" | "implement" | "implements" | "create" | "creates" | "support" | "supports" =>
+      return Section::Added,
+    "fix" | "fixes" | "fixed" | "resolve" | "resolves" | "correct" | "corrects" | "repair" | "prevent" | "prevents" | "handle" =>
+      return Section::Fixed,
+    "remove" | "removes" | "removed" | "delete" | "deletes" | "drop" | "drops" | "revert" | "reverts" => return Section::Removed,
+    "deprecate" | "deprecates" => return Section::Deprecated,
+    _ => {}
+  }
+
+  if parsed.kind.is_some() {
+    return Section::Changed;
+  }
+
+  if !files.is_empty() && files.iter().all(|f| f.operation_type == "added") {
+    Section::Added
+  } else if !files.is_empty() && files.iter().all(|f| f.operation_type == "deleted") {
+    Section::Removed
+  } else {
+    Section::Changed
+  }
+}
+
+/// Turns a commit subject into an entry text: conventional prefix and trailing
+/// period removed, first letter capitalized.
+fn entry_text(subject: &Subject) -> String {
+  let description = subject.description.trim().trim_end_matches('.');
+  let mut chars = description.chars();
+  let mut text = match chars.next() {
+    Some(first) => first.to_uppercase().chain(chars).collect(),
+    None => String::new()
+  };
+  if subject.breaking {
+    text.push_str(" (breaking)");
+  }
+  text
+}
+
+/// Whether a commit carries no changelog-worthy information of its own
+fn is_noise(subject: &str) -> bool {
+  let lower = subject.to_lowercase();
+  lower.starts_with("fixup!") || lower.starts_with("squash!") || lower.starts_with("merge ") || lower.starts_with("wip")
+}
+
+/// Builds an entry for a commit, or `None` for commits that do not belong in a changelog
+pub fn entry_for(subject: &str, files: &[FileWithScore]) -> Option<Entry> {
+  if is_noise(subject) {
+    return None;
+  }
+
+  let parsed = parse_subject(subject);
+  if matches!(parsed.kind.as_deref(), Some("chore" | "ci" | "test" | "style" | "build")) && !parsed.breaking {
+    return None;
+  }
+
+  let text = entry_text(&parsed);
+  if text.is_empty() {
+    return None;
+  }
+
+  Some(Entry {
+    section: classify(subject, files),
+    scope: parsed.scope,
+    text
+  })
+}
+
+/// Merges related entries: duplicates within a section are dropped and entries sharing a
+/// conventional commit scope are folded into one line.
+pub fn merge_entries(entries: Vec<Entry>) -> Vec<Entry> {
+  let mut merged: Vec<Entry> = Vec::new();
+
+  for entry in entries {
+    let duplicate = merged
+      .iter()
+      .any(|existing| existing.section == entry.section && existing.text.eq_ignore_ascii_case(&entry.text));
+    if duplicate {
+      continue;
+    }
+
+    let related = merged
+      .iter_mut()
+      .find(|existing| existing.section == entry.section && entry.scope.is_some() && existing.scope == entry.scope);
+    match related {
+      Some(existing) => {
+        let mut chars = entry.text.chars();
+        let lowered: String = match chars.next() {
+          Some(first) => first.to_lowercase().chain(chars).collect(),
+          None => String::new()
+        };
+        existing.text = format!("{}; {}", existing.text, lowered);
+      }
+      None => merged.push(entry)
+    }
+  }
+
+  merged
+}
+
+/// Renders a version section, e.g. `## [1.2.0] - 2026-06-17`, with only the non-empty
+/// subsections in Keep a Changelog order. `Unreleased` gets no date.
+pub fn render_section(version: &str, date: &str, entries: &[Entry]) -> String {
+  let header = if version.eq_ignore_ascii_case("unreleased") {
+    "## [Unreleased]\n".to_string()
+  } else {
+    format!("## [{}] - {date}\n", version.trim_start_matches('v'))
+  };
+
+  header + &render_entries(entries)
+}
+
+/// Renders the `### Added`, `### Fixed`, ... subsections of `entries`, each preceded by
+/// a blank line
+pub fn render_entries(entries: &[Entry]) -> String {
+  let mut output = String::new();
+  for section in Section::ALL {
+    let lines: Vec<&Entry> = entries
+      .iter()
+      .filter(|entry| entry.section == section)
+      .collect();
+    if lines.is_empty() {
+      continue;
+    }
+
+    output.push_str(&format!("\n### {section}\n\n"));
+    for entry in lines {
+      match &entry.scope {
+        Some(scope) => output.push_str(&format!("- **{scope}**: {}\n", entry.text)),
+        None => output.push_str(&format!("- {}\n", entry.text))
+      }
+    }
+  }
+
+  output
+}
+
+/// Formats a git timestamp (seconds since the epoch plus offset) as `YYYY-MM-DD`
+pub fn format_date(time: git2::Time) -> String {
+  let days = (time.seconds() + i64::from(time.offset_minutes()) * 60).div_euclid(86_400);
+
+  // Civil-from-days, valid for the proleptic Gregorian calendar
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let doe = z.rem_euclid(146_097);
+  let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 {
+    mp + 3
+  } else {
+    mp - 9
+  };
+  let year = yoe + era * 400 + i64::from(month <= 2);
+
+  format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Scores the files a commit changed relative to its first parent
+pub(crate) fn commit_files(repo: &Repository, commit: &Commit<'_>) -> Result<Vec<FileWithScore>> {
+  let parent_tree = match commit.parent(0) {
+    Ok(parent) => Some(parent.tree()?),
+    Err(_) => None
+  };
+  let patch = repo
+    .to_tree_diff(parent_tree, &commit.tree()?)?
+    .to_full_patch()?;
+  score_files_local(&patch)
+}
+
+/// Generates the changelog section for `range`, dated with the tip commit's date
+pub fn generate(repo: &Repository, range: &str, version: &str) -> Result<String> {
+  let (base, head) = resolve_range(repo, range)?;
+  let commits = range_commits(repo, &base, &head)?;
+  if commits.is_empty() {
+    bail!("No commits in range '{range}'");
+  }
+
+  let mut entries = Vec::new();
+  for commit in &commits {
+    let subject = commit.summary_bytes().unwrap_or_default().to_utf8();
+    let files = commit_files(repo, commit)?;
+    entries.extend(entry_for(&subject, &files));
+  }
+
+  let entries = merge_entries(entries);
+  if entries.is_empty() {
+    bail!("None of the {} commits in '{range}' belong in a changelog", commits.len());
+  }
+
+  Ok(render_section(version, &format_date(head.time()), &entries))
+}
+
+/// Inserts `section` above the newest version in the changelog at `path`, creating the
+/// file with the standard header when it does not exist yet.
+pub fn prepend(path: &Path, section: &str) -> Result<()> {
+  let existing = if path.exists() {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+  } else {
+    CHANGELOG_HEADER.to_string()
+  };
+
+  let heading = section.lines().next().unwrap_or_default();
+  let version = heading.split(']').next().unwrap_or(heading);
+  if existing.lines().any(|line| line.starts_with(version)) {
+    bail!("{} already has a {}] section", path.display(), version.trim_start_matches("## "));
+  }
+
+  // New versions go below a pending `[Unreleased]` section, everything else on top
+  let insert_at = existing
+    .match_indices("\n## [")
+    .map(|(index, _)| index)
+    .find(|&index| !existing[index + 1..].starts_with("## [Unreleased]") || heading.starts_with("## [Unreleased]"));
+
+  let updated = match insert_at {
+    Some(index) => format!("{}\n{}\n{}", &existing[..index], section, &existing[index + 1..]),
+    None => format!("{}\n\n{}", existing.trim_end(), section)
+  };
+
+  std::fs::write(path, updated).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn file(operation: &str) -> FileWithScore {
+    FileWithScore {
+      file_path:      "src/lib.rs".to_string(),
+      operation_type: operation.to_string(),
+      lines_added:    1,
+      lines_removed:  0,
+      file_category:  "source".to_string(),
+      summary:        String::new(),
+      impact_score:   0.5
+    }
+  }
+
+  #[test]
+  fn test_classify_by_message_then_diff() {
+    assert_eq!(classify("feat(hook): support amend", &[]), Section::Added);
+    assert_eq!(classify("fix: crash on empty diff", &[]), Section::Fixed);
+    assert_eq!(classify("Remove legacy prompt", &[]), Section::Removed);
+    assert_eq!(classify("Escape shell input to prevent injection", &[]), Section::Security);
+    assert_eq!(classify("refactor: split parser", &[]), Section::Changed);
+    assert_eq!(classify("[ABC-12] Add reword command", &[]), Section::Added);
+    assert_eq!(classify("Tweak things", &[file("added")]), Section::Added);
+    assert_eq!(classify("Tweak things", &[file("deleted")]), Section::Removed);
+    assert_eq!(classify("Tweak things", &[file("added"), file("modified")]), Section::Changed);
+  }
+
+  #[test]
+  fn test_entries_skip_noise_and_merge_related() {
+    let entries: Vec<Entry> = [
+      "feat(config): add unset command.", "feat(config): Add timeout setter", "fixup! feat(config): add unset command", "chore: bump deps",
+      "Fix typo in README", "fix typo in README"
+    ]
+    .iter()
+    .filter_map(|subject| entry_for(subject, &[]))
+    .collect();
+
+    let merged = merge_entries(entries);
+    assert_eq!(merged.len(), 2);
+    assert_eq!(merged[0].text, "Add unset command; add timeout setter");
+    assert_eq!(merged[0].scope.as_deref(), Some("config"));
+    assert_eq!(merged[1].section, Section::Fixed);
+  }
+
+  #[test]
+  fn test_render_section_in_keep_a_changelog_order() {
+    let entries = vec![
+      Entry {
+        section: Section::Fixed,
+        scope:   None,
+        text:    "Fix crash".into()
+      },
+      Entry {
+        section: Section::Added,
+        scope:   Some("hook".into()),
+        text:    "Add picker".into()
+      },
+    ];
+    assert_eq!(
+      render_section("v1.3.0", "2026-10-17", &entries),
+      "## [1.3.0] - 2026-10-17\n\n### Added\n\n- **hook**: Add picker\n\n### Fixed\n\n- Fix crash\n"
+    );
+    assert!(render_section("Unreleased", "2026-10-17", &entries).starts_with("## [Unreleased]\n\n### Added"));
+  }
+
+  #[test]
+  fn test_format_date() {
+    assert_eq!(format_date(git2::Time::new(0, 0)), "1970-01-01");
+    assert_eq!(format_date(git2::Time::new(1_781_654_400, 0)), "2026-06-17");
+    // Late evening in UTC-5 is the next day in UTC, but the author's date is kept
+    assert_eq!(format_date(git2::Time::new(1_781_654_400 + 3_600, -300)), "2026-06-16");
+  }
+
+  #[test]
+  fn test_prepend_above_latest_version() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("CHANGELOG.md");
+    std::fs::write(&path, format!("{CHANGELOG_HEADER}\n## [1.0.0] - 2026-01-01\n\n### Added\n\n- First\n")).unwrap();
+
+    prepend(&path, "## [1.1.0] - 2026-02-01\n\n### Fixed\n\n- Second\n").unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    let new = content.find("## [1.1.0]").unwrap();
+    let old = content.find("## [1.0.0]").unwrap();
+    assert!(content.starts_with("# Changelog"));
+    assert!(new < old);
+    assert!(content.contains("- Second\n\n## [1.0.0]"));
+
+    assert!(prepend(&path, "## [1.1.0] - 2026-02-01\n").is_err());
+
+    prepend(&path, "## [Unreleased]\n\n### Added\n\n- Third\n").unwrap();
+    prepend(&path, "## [1.2.0] - 2026-03-01\n\n### Added\n\n- Fourth\n").unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    let unreleased = content.find("## [Unreleased]").unwrap();
+    let newest = content.find("## [1.2.0]").unwrap();
+    assert!(unreleased < newest && newest < content.find("## [1.1.0]").unwrap());
+  }
+}
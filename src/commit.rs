@@ -3,10 +3,11 @@ use maplit::hashmap;
 use mustache;
 use async_openai::Client;
 
-use crate::{config, debug_output, openai, profile};
+use crate::{config, debug_output, language, openai, profile};
 use crate::model::Model;
 use crate::config::AppConfig;
-use crate::multi_step_integration::{generate_commit_message_local, generate_commit_message_multi_step};
+use crate::generation::{CommitResponse, Strategy};
+use crate::multi_step_integration::{generate_commit_response_local, generate_commit_response_multi_step};
 
 /// The instruction template included at compile time
 const INSTRUCTION_TEMPLATE: &str = include_str!("../resources/prompt.md");
@@ -20,15 +21,25 @@ const INSTRUCTION_TEMPLATE: &str = include_str!("../resources/prompt.md");
 /// Note: This function is public only for testing purposes
 #[doc(hidden)]
 pub fn get_instruction_template() -> Result<String> {
+  instruction_template(
+    config::APP_CONFIG.max_commit_length,
+    config::APP_CONFIG
+      .language
+      .as_deref()
+      .unwrap_or(config::DEFAULT_LANGUAGE)
+  )
+}
+
+/// The instruction template for a maximum subject length and a language
+fn instruction_template(max_length: Option<usize>, language: &str) -> Result<String> {
   profile!("Generate instruction template");
-  let max_length = config::APP_CONFIG
-    .max_commit_length
-    .unwrap_or(72)
-    .to_string();
+  let max_length = max_length.unwrap_or(72).to_string();
+  let language = language::name(language);
   let template = mustache::compile_str(INSTRUCTION_TEMPLATE)
     .map_err(|e| anyhow!("Template compilation error: {}", e))?
     .render_to_string(&hashmap! {
-      "max_length" => max_length
+      "max_length" => max_length,
+      "language" => language
     })
     .map_err(|e| anyhow!("Template rendering error: {}", e))?;
   Ok(template)
@@ -47,14 +58,17 @@ pub fn get_instruction_template() -> Result<String> {
 /// Note: This function is public only for testing purposes
 #[doc(hidden)]
 pub fn create_commit_request(diff: String, max_tokens: usize, model: Model) -> Result<openai::Request> {
+  Ok(commit_request(diff, max_tokens, model, get_instruction_template()?))
+}
+
+fn commit_request(diff: String, max_tokens: usize, model: Model, template: String) -> openai::Request {
   profile!("Prepare OpenAI request");
-  let template = get_instruction_template()?;
-  Ok(openai::Request {
+  openai::Request {
     system: template,
     prompt: diff,
     max_tokens: max_tokens.try_into().unwrap_or(u16::MAX),
     model
-  })
+  }
 }
 
 /// Generates a commit message using the AI model.
@@ -74,6 +88,19 @@ pub fn create_commit_request(diff: String, max_tokens: usize, model: Model) -> R
 /// - max_tokens is 0
 /// - OpenAI API call fails
 pub async fn generate(patch: String, remaining_tokens: usize, model: Model, settings: Option<&AppConfig>) -> Result<openai::Response> {
+  generate_response(patch, remaining_tokens, model, settings)
+    .await
+    .map(|response| openai::Response { response: response.message })
+}
+
+/// Same as [`generate`], but keeps everything the pipeline produced: the reasoning,
+/// the candidates, the per-file summaries and which strategy ended up being used.
+///
+/// # Returns
+/// * `Result<CommitResponse>` - The generated commit message and its context or an error
+pub async fn generate_response(
+  patch: String, remaining_tokens: usize, model: Model, settings: Option<&AppConfig>
+) -> Result<CommitResponse> {
   profile!("Generate commit message");
 
   if remaining_tokens == 0 {
@@ -84,21 +111,17 @@ pub async fn generate(patch: String, remaining_tokens: usize, model: Model, sett
   let max_length = settings
     .and_then(|s| s.max_commit_length)
     .or(config::APP_CONFIG.max_commit_length);
+  let language = settings
+    .and_then(|s| s.language.as_deref())
+    .or(config::APP_CONFIG.language.as_deref())
+    .unwrap_or(config::DEFAULT_LANGUAGE);
 
   // Check if we have a valid API key configuration
   let has_valid_api_key = if let Some(custom_settings) = settings {
-    custom_settings
-      .openai_api_key
-      .as_ref()
-      .map(|key| !key.is_empty() && key != "<PLACE HOLDER FOR YOUR API KEY>")
-      .unwrap_or(false)
+    custom_settings.has_api_key()
   } else {
     // Check environment variable or config
-    config::APP_CONFIG
-      .openai_api_key
-      .as_ref()
-      .map(|key| !key.is_empty() && key != "<PLACE HOLDER FOR YOUR API KEY>")
-      .unwrap_or(false)
+    config::APP_CONFIG.has_api_key()
       || std::env::var("OPENAI_API_KEY")
         .map(|key| !key.is_empty())
         .unwrap_or(false)
@@ -110,31 +133,29 @@ pub async fn generate(patch: String, remaining_tokens: usize, model: Model, sett
 
   // Use custom settings if provided
   if let Some(custom_settings) = settings {
-    if let Some(api_key) = &custom_settings.openai_api_key {
-      if !api_key.is_empty() && api_key != "<PLACE HOLDER FOR YOUR API KEY>" {
-        match openai::create_openai_config(custom_settings) {
-          Ok(config) => {
-            let client = Client::with_config(config);
-            let model_str = model.to_string();
-
-            match generate_commit_message_multi_step(&client, &model_str, &patch, max_length).await {
-              Ok(message) => return Ok(openai::Response { response: message }),
-              Err(e) => {
-                // Check if it's an API key error
-                if e.to_string().contains("invalid_api_key") || e.to_string().contains("Incorrect API key") {
-                  bail!("Invalid OpenAI API key. Please check your API key configuration.");
-                }
-                log::warn!("Multi-step generation with custom settings failed: {e}");
-                if let Some(session) = debug_output::debug_session() {
-                  session.set_multi_step_error(e.to_string());
-                }
+    if custom_settings.has_api_key() {
+      match openai::create_openai_config(custom_settings) {
+        Ok(config) => {
+          let client = Client::with_config(config);
+          let model_str = model.to_string();
+
+          match generate_commit_response_multi_step(&client, &model_str, &patch, max_length, language).await {
+            Ok(response) => return Ok(response),
+            Err(e) => {
+              // Check if it's an API key error
+              if e.to_string().contains("invalid_api_key") || e.to_string().contains("Incorrect API key") {
+                bail!("Invalid OpenAI API key. Please check your API key configuration.");
+              }
+              log::warn!("Multi-step generation with custom settings failed: {e}");
+              if let Some(session) = debug_output::debug_session() {
+                session.set_multi_step_error(e.to_string());
               }
             }
           }
-          Err(e) => {
-            // If config creation fails due to API key, propagate the error
-            return Err(e);
-          }
+        }
+        Err(e) => {
+          // If config creation fails due to API key, propagate the error
+          return Err(e);
         }
       }
     }
@@ -147,17 +168,21 @@ pub async fn generate(patch: String, remaining_tokens: usize, model: Model, sett
     // Fall back to the environment variable when the config holds no usable key.
     let client = match openai::create_openai_config(&config::APP_CONFIG) {
       Ok(config) => Some(Client::with_config(config)),
-      Err(_) => match std::env::var("OPENAI_API_KEY") {
-        Ok(key) if !key.is_empty() => Some(Client::new()),
-        _ => None
-      }
+      // A configured key command or file that cannot be read is an error, not a reason
+      // to quietly fall back to the local generator
+      Err(e) if config::APP_CONFIG.has_api_key() => return Err(e),
+      Err(_) =>
+        match std::env::var("OPENAI_API_KEY") {
+          Ok(key) if !key.is_empty() => Some(Client::new()),
+          _ => None
+        },
     };
 
     if let Some(client) = client {
       let model_str = model.to_string();
 
-      match generate_commit_message_multi_step(&client, &model_str, &patch, max_length).await {
-        Ok(message) => return Ok(openai::Response { response: message }),
+      match generate_commit_response_multi_step(&client, &model_str, &patch, max_length, language).await {
+        Ok(response) => return Ok(response),
         Err(e) => {
           // Check if it's an API key error
           if e.to_string().contains("invalid_api_key") || e.to_string().contains("Incorrect API key") {
@@ -173,8 +198,8 @@ pub async fn generate(patch: String, remaining_tokens: usize, model: Model, sett
   }
 
   // Try local multi-step generation
-  match generate_commit_message_local(&patch, max_length) {
-    Ok(message) => return Ok(openai::Response { response: message }),
+  match generate_commit_response_local(&patch, max_length, language) {
+    Ok(response) => return Ok(response),
     Err(e) => {
       log::warn!("Local multi-step generation failed: {e}");
     }
@@ -185,11 +210,11 @@ pub async fn generate(patch: String, remaining_tokens: usize, model: Model, sett
     session.set_single_step_success(true);
   }
 
-  // Fallback to original single-step approach
-  let request = create_commit_request(patch, remaining_tokens, model)?;
+  // Fallback to original single-step approach, with the same length and language
+  let request = commit_request(patch, remaining_tokens, model, instruction_template(max_length, language)?);
 
   // Use custom settings if provided, otherwise use global config
-  match settings {
+  let response = match settings {
     Some(custom_settings) => {
       // Create a client with custom settings
       match openai::create_openai_config(custom_settings) {
@@ -201,7 +226,28 @@ pub async fn generate(patch: String, remaining_tokens: usize, model: Model, sett
       // Use the default global config
       openai::call(request).await
     }
-  }
+  }?;
+
+  Ok(CommitResponse {
+    message:    response.response,
+    reasoning:  String::new(),
+    files:      Default::default(),
+    candidates: Vec::new(),
+    strategy:   Strategy::SingleStep
+  })
+}
+
+/// Returns the number of tokens left for the diff once the instruction template is
+/// accounted for, honoring the configured `max_tokens` (or the model's context size).
+pub fn remaining_tokens(model: &Model) -> Result<usize> {
+  remaining_tokens_for(model, &config::APP_CONFIG)
+}
+
+/// [`remaining_tokens`] with the `max_tokens` of `settings`
+pub fn remaining_tokens_for(model: &Model, settings: &AppConfig) -> Result<usize> {
+  let used_tokens = token_used(model)?;
+  let max_tokens = settings.max_tokens.unwrap_or(model.context_size());
+  Ok(max_tokens.saturating_sub(used_tokens).max(512)) // Ensure minimum 512 tokens
 }
 
 pub fn token_used(model: &Model) -> Result<usize> {
@@ -229,12 +275,13 @@ mod tests {
   async fn test_missing_api_key_error() {
     // Create settings with no API key
     let settings = AppConfig {
-      openai_api_key:    None,
-      openai_base_url:   None,
-      model:             Some("gpt-4.1-mini".to_string()),
-      max_tokens:        Some(1024),
+      openai_api_key: None,
+      openai_base_url: None,
+      model: Some("gpt-4.1-mini".to_string()),
+      max_tokens: Some(1024),
       max_commit_length: Some(72),
-      timeout:           Some(30)
+      timeout: Some(30),
+      ..Default::default()
     };
 
     // Temporarily clear the environment variable
@@ -268,12 +315,13 @@ mod tests {
   async fn test_invalid_api_key_error() {
     // Create settings with invalid API key
     let settings = AppConfig {
-      openai_api_key:    Some("<PLACE HOLDER FOR YOUR API KEY>".to_string()),
-      openai_base_url:   None,
-      model:             Some("gpt-4.1-mini".to_string()),
-      max_tokens:        Some(1024),
+      openai_api_key: Some("<PLACE HOLDER FOR YOUR API KEY>".to_string()),
+      openai_base_url: None,
+      model: Some("gpt-4.1-mini".to_string()),
+      max_tokens: Some(1024),
       max_commit_length: Some(72),
-      timeout:           Some(30)
+      timeout: Some(30),
+      ..Default::default()
     };
 
     // Test that generate returns an error for invalid API key
@@ -293,4 +341,11 @@ mod tests {
       error_message
     );
   }
+
+  #[test]
+  fn test_instruction_template_uses_the_given_language_and_length() {
+    let template = instruction_template(Some(50), "sv").unwrap();
+    assert!(template.contains("Write the commit message in Swedish"));
+    assert!(template.contains("50"));
+  }
 }
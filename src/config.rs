@@ -1,37 +1,305 @@
+use std::collections::BTreeMap;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs::File;
+use std::fmt;
+use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 use config::{Config, FileFormat};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use lazy_static::lazy_static;
 use console::Emoji;
 
+use crate::api_key::KeySource;
+use crate::model::Model;
+
 // Constants
 const DEFAULT_TIMEOUT: i64 = 30;
 const DEFAULT_MAX_COMMIT_LENGTH: i64 = 72;
 const DEFAULT_MAX_TOKENS: i64 = 2024;
-const DEFAULT_MODEL: &str = "gpt-4.1-mini"; // Matches Model::default()
-const DEFAULT_API_KEY: &str = "<PLACE HOLDER FOR YOUR API KEY>";
+pub const DEFAULT_MODEL: &str = "gpt-4.1-mini"; // Matches Model::default()
+pub const DEFAULT_API_KEY: &str = "<PLACE HOLDER FOR YOUR API KEY>";
+pub const DEFAULT_BRANCH_PATTERN: &str = "<type>/<slug>";
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Version of the `config.toml` format this build reads and writes
+pub const CONFIG_VERSION: i64 = 1;
+
+/// Selects a profile for a single invocation, overriding `git ai profile use`
+pub const PROFILE_ENV: &str = "GIT_AI_PROFILE";
 
-#[derive(Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+// Bounds enforced before a value is written to disk
+const MIN_COMMIT_LENGTH: usize = 10;
+const MAX_COMMIT_LENGTH: usize = 500;
+const MIN_TIMEOUT: usize = 1;
+const MAX_TIMEOUT: usize = 3600;
+
+// TOML has no null, and a `None` in a configuration layer would hide the value of a lower
+// one; every field is skipped entirely when unset so a partial config (e.g. after
+// `git ai config unset`) still round-trips and a profile only overrides what it sets.
+#[derive(Clone, Default, Deserialize, PartialEq, Eq, Serialize)]
 pub struct AppConfig {
-  pub openai_api_key:    Option<String>,
-  // serde_ini cannot serialize `None`; skip the field entirely when unset so a
-  // config without a base URL still round-trips (and `save()` does not error).
   #[serde(skip_serializing_if = "Option::is_none")]
-  pub openai_base_url:   Option<String>,
-  pub model:             Option<String>,
-  pub max_tokens:        Option<usize>,
-  pub max_commit_length: Option<usize>,
-  pub timeout:           Option<usize>
+  pub openai_api_key:           Option<String>,
+  /// A shell command that prints the API key, run when a request is made
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub openai_api_key_command:   Option<String>,
+  /// A file holding the API key; it must not be accessible by other users
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub openai_api_key_file:      Option<String>,
+  /// The name of an environment variable holding the API key
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub openai_api_key_env:       Option<String>,
+  /// Makes `git ai config set openai-api-key` refuse to store a key in plain text
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub forbid_plaintext_api_key: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub openai_base_url:          Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub model:                    Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_tokens:               Option<usize>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_commit_length:        Option<usize>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub timeout:                  Option<usize>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub branch_pattern:           Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub conventional_commits:     Option<bool>,
+  /// The language commit messages are written in, e.g. `sv` or `Swedish`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub language:                 Option<String>,
+  /// The profile whose values apply over the ones above, chosen with `git ai profile use`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub profile:                  Option<String>,
+  /// Named sets of the values above, stored as `[profiles.<name>]` tables
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub profiles:                 BTreeMap<String, AppConfig>
+}
+
+// Written by hand so the API key never ends up in logs or error messages
+impl fmt::Debug for AppConfig {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("AppConfig")
+      .field("openai_api_key", &self.openai_api_key.as_deref().map(mask_secret))
+      .field("openai_api_key_command", &self.openai_api_key_command.as_deref().map(mask_command))
+      .field("openai_api_key_file", &self.openai_api_key_file)
+      .field("openai_api_key_env", &self.openai_api_key_env)
+      .field("forbid_plaintext_api_key", &self.forbid_plaintext_api_key)
+      .field("openai_base_url", &self.openai_base_url)
+      .field("model", &self.model)
+      .field("max_tokens", &self.max_tokens)
+      .field("max_commit_length", &self.max_commit_length)
+      .field("timeout", &self.timeout)
+      .field("branch_pattern", &self.branch_pattern)
+      .field("conventional_commits", &self.conventional_commits)
+      .field("language", &self.language)
+      .field("profile", &self.profile)
+      .field("profiles", &self.profiles)
+      .finish()
+  }
+}
+
+/// A configuration option as it is named on the command line (`git ai config set <key>`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigKey {
+  Model,
+  MaxTokens,
+  MaxCommitLength,
+  OpenaiApiKey,
+  OpenaiApiKeyCommand,
+  OpenaiApiKeyFile,
+  OpenaiApiKeyEnv,
+  ForbidPlaintextApiKey,
+  OpenaiBaseUrl,
+  Timeout,
+  BranchPattern,
+  ConventionalCommits,
+  Language
+}
+
+impl ConfigKey {
+  pub const ALL: [ConfigKey; 13] = [
+    ConfigKey::Model,
+    ConfigKey::MaxTokens,
+    ConfigKey::MaxCommitLength,
+    ConfigKey::OpenaiApiKey,
+    ConfigKey::OpenaiApiKeyCommand,
+    ConfigKey::OpenaiApiKeyFile,
+    ConfigKey::OpenaiApiKeyEnv,
+    ConfigKey::ForbidPlaintextApiKey,
+    ConfigKey::OpenaiBaseUrl,
+    ConfigKey::Timeout,
+    ConfigKey::BranchPattern,
+    ConfigKey::ConventionalCommits,
+    ConfigKey::Language
+  ];
+
+  /// The name used on the command line, e.g. `max-tokens`
+  pub fn name(&self) -> &'static str {
+    match self {
+      ConfigKey::Model => "model",
+      ConfigKey::MaxTokens => "max-tokens",
+      ConfigKey::MaxCommitLength => "max-commit-length",
+      ConfigKey::OpenaiApiKey => "openai-api-key",
+      ConfigKey::OpenaiApiKeyCommand => "openai-api-key-command",
+      ConfigKey::OpenaiApiKeyFile => "openai-api-key-file",
+      ConfigKey::OpenaiApiKeyEnv => "openai-api-key-env",
+      ConfigKey::ForbidPlaintextApiKey => "forbid-plaintext-api-key",
+      ConfigKey::OpenaiBaseUrl => "openai-base-url",
+      ConfigKey::Timeout => "timeout",
+      ConfigKey::BranchPattern => "branch-pattern",
+      ConfigKey::ConventionalCommits => "conventional-commits",
+      ConfigKey::Language => "language"
+    }
+  }
+
+  /// The field name used in `config.toml` and (upper-cased, `APP_`-prefixed) in the environment
+  pub fn field(&self) -> &'static str {
+    match self {
+      ConfigKey::Model => "model",
+      ConfigKey::MaxTokens => "max_tokens",
+      ConfigKey::MaxCommitLength => "max_commit_length",
+      ConfigKey::OpenaiApiKey => "openai_api_key",
+      ConfigKey::OpenaiApiKeyCommand => "openai_api_key_command",
+      ConfigKey::OpenaiApiKeyFile => "openai_api_key_file",
+      ConfigKey::OpenaiApiKeyEnv => "openai_api_key_env",
+      ConfigKey::ForbidPlaintextApiKey => "forbid_plaintext_api_key",
+      ConfigKey::OpenaiBaseUrl => "openai_base_url",
+      ConfigKey::Timeout => "timeout",
+      ConfigKey::BranchPattern => "branch_pattern",
+      ConfigKey::ConventionalCommits => "conventional_commits",
+      ConfigKey::Language => "language"
+    }
+  }
+
+  fn default_value(&self) -> Option<String> {
+    match self {
+      ConfigKey::Model => Some(DEFAULT_MODEL.to_string()),
+      ConfigKey::MaxTokens => Some(DEFAULT_MAX_TOKENS.to_string()),
+      ConfigKey::MaxCommitLength => Some(DEFAULT_MAX_COMMIT_LENGTH.to_string()),
+      ConfigKey::OpenaiApiKey => Some(DEFAULT_API_KEY.to_string()),
+      ConfigKey::OpenaiApiKeyCommand | ConfigKey::OpenaiApiKeyFile | ConfigKey::OpenaiApiKeyEnv => None,
+      ConfigKey::ForbidPlaintextApiKey => Some("false".to_string()),
+      ConfigKey::OpenaiBaseUrl => None,
+      ConfigKey::Timeout => Some(DEFAULT_TIMEOUT.to_string()),
+      ConfigKey::BranchPattern => Some(DEFAULT_BRANCH_PATTERN.to_string()),
+      ConfigKey::ConventionalCommits => Some("false".to_string()),
+      ConfigKey::Language => Some(DEFAULT_LANGUAGE.to_string())
+    }
+  }
+}
+
+impl FromStr for ConfigKey {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    let name = s.trim().to_lowercase().replace('_', "-");
+    match ConfigKey::ALL.iter().find(|key| key.name() == name) {
+      Some(key) => Ok(*key),
+      None => {
+        let known: Vec<&str> = ConfigKey::ALL.iter().map(ConfigKey::name).collect();
+        bail!("Unknown configuration key '{}'. Known keys: {}", s, known.join(", "))
+      }
+    }
+  }
+}
+
+impl fmt::Display for ConfigKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+/// Where an effective configuration value was resolved from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+  Default,
+  File,
+  Environment,
+  /// The active `[profiles.<name>]` table of `config.toml`
+  Profile,
+  /// An `ai.*` key in the repository's `.git/config`
+  Local
+}
+
+impl fmt::Display for ConfigSource {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      ConfigSource::Default => "default",
+      ConfigSource::File => "file",
+      ConfigSource::Environment => "environment",
+      ConfigSource::Profile => "profile",
+      ConfigSource::Local => "local"
+    };
+    write!(f, "{name}")
+  }
+}
+
+/// An effective configuration value together with the layer it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigEntry {
+  pub key:    ConfigKey,
+  pub value:  Option<String>,
+  pub source: ConfigSource
+}
+
+impl ConfigEntry {
+  /// The value as it is safe to print: the API key and the arguments of the key command
+  /// are masked and unset values are marked.
+  pub fn display_value(&self) -> String {
+    match (&self.value, self.key) {
+      (None, _) => "(not set)".to_string(),
+      (Some(value), ConfigKey::OpenaiApiKey) if value.is_empty() || value == DEFAULT_API_KEY => "(not set)".to_string(),
+      (Some(value), ConfigKey::OpenaiApiKey) => mask_secret(value),
+      (Some(value), ConfigKey::OpenaiApiKeyCommand) => mask_command(value),
+      (Some(value), _) => value.clone()
+    }
+  }
+}
+
+/// Masks all but a short prefix and suffix of a secret, e.g. `sk-…wxyz`
+pub fn mask_secret(secret: &str) -> String {
+  let chars: Vec<char> = secret.chars().collect();
+  if chars.len() < 12 {
+    return "*".repeat(chars.len().max(4));
+  }
+
+  let prefix: String = chars[..3].iter().collect();
+  let suffix: String = chars[chars.len() - 4..].iter().collect();
+  format!("{prefix}…{suffix}")
+}
+
+/// Keeps only the program of a key command, e.g. `pass …`, since its arguments may hold
+/// the key itself
+pub fn mask_command(command: &str) -> String {
+  let mut words = command.split_whitespace();
+  let program = words.next().unwrap_or_default();
+  match words.next() {
+    Some(_) => format!("{program} …"),
+    None => program.to_string()
+  }
+}
+
+/// Where `git ai config set` and `unset` write a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigScope {
+  /// The top level of `config.toml`
+  Global,
+  /// The `ai.*` keys in the current repository's `.git/config`
+  Local,
+  /// A `[profiles.<name>]` table of `config.toml`
+  Profile(String)
 }
 
 #[derive(Debug)]
 pub struct ConfigPaths {
-  pub dir:  PathBuf,
-  pub file: PathBuf
+  pub dir:    PathBuf,
+  pub file:   PathBuf,
+  /// `config.ini`, written by releases before `config.toml`
+  pub legacy: PathBuf
 }
 
 lazy_static! {
@@ -39,49 +307,329 @@ lazy_static! {
   pub static ref APP_CONFIG: AppConfig = AppConfig::new().expect("Failed to load config");
 }
 
+/// Path of the user configuration file, `~/.config/git-ai/config.toml`
+pub fn config_file_path() -> PathBuf {
+  PATHS.file.clone()
+}
+
+/// Directory of the user configuration, `~/.config/git-ai`
+pub fn config_dir() -> PathBuf {
+  PATHS.dir.clone()
+}
+
+/// Deletes `config.toml`, and a `config.ini` that was never migrated, so the defaults apply
+/// again. The rest of the directory, such as the usage ledger, the price table and
+/// `config.ini.bak`, is kept. Returns whether there was anything to delete.
+pub fn reset() -> Result<bool> {
+  let mut removed = false;
+  for path in [&PATHS.file, &PATHS.legacy] {
+    if path.exists() {
+      std::fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+      removed = true;
+    }
+  }
+  Ok(removed)
+}
+
 impl ConfigPaths {
   fn new() -> Self {
     let dir = home::home_dir()
       .expect("Failed to determine home directory")
       .join(".config/git-ai");
-    let file = dir.join("config.ini");
-    Self { dir, file }
+    let file = dir.join("config.toml");
+    let legacy = dir.join("config.ini");
+    Self { dir, file, legacy }
   }
 
+  /// Creates `config.toml`, migrating `config.ini` the first time, and checks that this
+  /// build understands its version
   fn ensure_exists(&self) -> Result<()> {
     if !self.dir.exists() {
       std::fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create config directory at {:?}", self.dir))?;
     }
     if !self.file.exists() {
-      File::create(&self.file).with_context(|| format!("Failed to create config file at {:?}", self.file))?;
+      if self.legacy.exists() {
+        let backup = migrate(&self.legacy, &self.file)?;
+        eprintln!(
+          "Moved the configuration to {}; the old file is kept as {}",
+          self.file.display(),
+          backup.display()
+        );
+      } else {
+        std::fs::write(&self.file, AppConfig::default().to_toml()?)
+          .with_context(|| format!("Failed to create config file at {:?}", self.file))?;
+      }
     }
-    Ok(())
+    check_version(&self.file)
+  }
+}
+
+/// Converts a `config.ini` written by an earlier release into `config.toml` and renames it
+/// to `config.ini.bak`. Values are carried over as stored, without validating them, so a
+/// config that loaded before keeps loading. Returns the path of the backup.
+fn migrate(ini: &Path, toml: &Path) -> Result<PathBuf> {
+  let stored: AppConfig = Config::builder()
+    .add_source(config::File::from(ini).format(FileFormat::Ini))
+    .build()
+    .and_then(Config::try_deserialize)
+    .with_context(|| format!("Failed to read {} to migrate it to {}", ini.display(), toml.display()))?;
+
+  std::fs::write(toml, stored.to_toml()?).with_context(|| format!("Failed to write {}", toml.display()))?;
+  let backup = ini.with_extension("ini.bak");
+  std::fs::rename(ini, &backup).with_context(|| format!("Failed to move {} to {}", ini.display(), backup.display()))?;
+  Ok(backup)
+}
+
+/// Fails when the file at `path` was written by a newer git-ai with a format this build
+/// does not know. A file without a version is read as the current one.
+fn check_version(path: &Path) -> Result<()> {
+  let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+  let table: toml::Table = toml::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))?;
+  match table.get("version") {
+    None => Ok(()),
+    Some(toml::Value::Integer(1..=CONFIG_VERSION)) => Ok(()),
+    Some(toml::Value::Integer(version)) if *version > CONFIG_VERSION =>
+      bail!(
+        "{} is configuration version {version}, but this git-ai only understands version {CONFIG_VERSION}. \
+         Upgrade git-ai, e.g. with `cargo install git-ai`",
+        path.display()
+      ),
+    Some(version) => bail!("Invalid version {version} in {}: expected {CONFIG_VERSION}", path.display())
   }
 }
 
 impl AppConfig {
   pub fn new() -> Result<Self> {
+    Self::layered(git2::Repository::open_from_env().ok().as_ref())
+  }
+
+  /// The configuration as [`AppConfig::new`] resolves it, but with the `ai.*` keys of
+  /// `repo` rather than those of the repository git-ai was started in
+  pub fn for_repo(repo: &git2::Repository) -> Result<Self> {
+    Self::layered(Some(repo))
+  }
+
+  fn layered(repo: Option<&git2::Repository>) -> Result<Self> {
     dotenv::dotenv().ok();
     PATHS.ensure_exists()?;
 
+    let stored = Self::from_file()?;
+    let profile = stored.checked_profile()?;
+
     let config = Config::builder()
-      .add_source(config::Environment::with_prefix("APP").try_parsing(true))
-      .add_source(config::File::new(PATHS.file.to_string_lossy().as_ref(), FileFormat::Ini))
-      .set_default("language", "en")?
+      .add_source(env_source())
+      .add_source(file_source())
+      .add_source(profile_source(profile.as_ref().map(|name| &stored.profiles[name]))?)
+      .add_source(local_source(repo)?)
+      .set_default("language", DEFAULT_LANGUAGE)?
       .set_default("timeout", DEFAULT_TIMEOUT)?
       .set_default("max_commit_length", DEFAULT_MAX_COMMIT_LENGTH)?
       .set_default("max_tokens", DEFAULT_MAX_TOKENS)?
       .set_default("model", DEFAULT_MODEL)?
       .set_default("openai_api_key", DEFAULT_API_KEY)?
+      .set_default("branch_pattern", DEFAULT_BRANCH_PATTERN)?
+      .set_default("conventional_commits", false)?
+      .set_default("forbid_plaintext_api_key", false)?
       .build()?;
 
-    config
+    let mut app: AppConfig = config
+      .try_deserialize()
+      .context("Failed to deserialize existing config. Please run `git ai config reset` and try again")?;
+    app.profile = profile;
+    Ok(app)
+  }
+
+  /// Resolves every configuration key and reports which layer its value came from.
+  /// Later layers win: defaults, then `APP_*` environment variables, then `config.toml`,
+  /// then the active profile, then the `ai.*` keys in the current repository's `.git/config`.
+  pub fn entries() -> Result<Vec<ConfigEntry>> {
+    dotenv::dotenv().ok();
+    PATHS.ensure_exists()?;
+
+    let local = local_source(git2::Repository::open_from_env().ok().as_ref())?;
+    let file = Config::builder().add_source(file_source()).build()?;
+    let stored = Self::from_file()?;
+    let profile = profile_source(
+      stored
+        .checked_profile()?
+        .map(|name| &stored.profiles[&name])
+    )?;
+    let env = Config::builder().add_source(env_source()).build()?;
+
+    let layers =
+      [(ConfigSource::Local, &local), (ConfigSource::Profile, &profile), (ConfigSource::File, &file), (ConfigSource::Environment, &env)];
+    Ok(
+      ConfigKey::ALL
+        .iter()
+        .map(|key| resolve_entry(*key, &layers))
+        .collect()
+    )
+  }
+
+  /// Resolves a single configuration key, see [`AppConfig::entries`]
+  pub fn entry(key: ConfigKey) -> Result<ConfigEntry> {
+    Self::entries()?
+      .into_iter()
+      .find(|entry| entry.key == key)
+      .with_context(|| format!("Failed to resolve configuration key {key}"))
+  }
+
+  /// Loads only what is stored in `config.toml`, without defaults, environment or repository
+  /// overrides, so saving it back never copies those layers into the global file.
+  pub fn from_file() -> Result<Self> {
+    PATHS.ensure_exists()?;
+
+    Config::builder()
+      .add_source(file_source())
+      .build()?
       .try_deserialize()
       .context("Failed to deserialize existing config. Please run `git ai config reset` and try again")
   }
 
+  /// The profile in effect: `GIT_AI_PROFILE` if set, else the one chosen with
+  /// `git ai profile use`
+  pub fn active_profile(&self) -> Option<String> {
+    profile_from_env().or_else(|| self.profile.clone())
+  }
+
+  /// [`AppConfig::active_profile`], failing when it names a profile that does not exist
+  /// rather than silently using the top-level values
+  fn checked_profile(&self) -> Result<Option<String>> {
+    match self.active_profile() {
+      Some(name) if !self.profiles.contains_key(&name) => bail!("{}", unknown_profile(&name, &self.profiles)),
+      name => Ok(name)
+    }
+  }
+
+  /// Makes `name` the profile applied by default
+  pub fn use_profile(name: &str) -> Result<()> {
+    let mut stored = Self::from_file()?;
+    if !stored.profiles.contains_key(name) {
+      bail!("{}", unknown_profile(name, &stored.profiles));
+    }
+    stored.profile = Some(name.to_string());
+    stored.save()
+  }
+
+  /// Whether a key is configured, either stored or as a [`KeySource`], without reading it
+  pub fn has_api_key(&self) -> bool {
+    KeySource::of(self).is_some() || self.plaintext_api_key().is_some()
+  }
+
+  /// The API key for a request. Key sources are only read here, so a key command runs
+  /// once a request is actually made, not whenever the configuration is loaded.
+  pub fn api_key(&self) -> Result<Option<String>> {
+    match KeySource::of(self) {
+      Some(source) => source.resolve().map(Some),
+      None => Ok(self.plaintext_api_key().map(str::to_string))
+    }
+  }
+
+  fn plaintext_api_key(&self) -> Option<&str> {
+    self
+      .openai_api_key
+      .as_deref()
+      .map(str::trim)
+      .filter(|key| !key.is_empty() && *key != DEFAULT_API_KEY)
+  }
+
+  /// Fails when `forbid-plaintext-api-key` is on in any layer of the configuration
+  fn check_plaintext_key_allowed() -> Result<()> {
+    if Self::new()?.forbid_plaintext_api_key == Some(true) {
+      bail!(
+        "forbid-plaintext-api-key is on, so the API key cannot be stored in the configuration. \
+         Use `git ai config set openai-api-key-command <command>`, `openai-api-key-file <path>` or `openai-api-key-env <name>`"
+      );
+    }
+    Ok(())
+  }
+
+  /// Takes the endpoint and API key of `other` where it sets them, e.g. to reach the
+  /// endpoint of a profile that is not the active one
+  pub fn use_endpoint_of(&mut self, other: &AppConfig) {
+    if other.has_api_key() {
+      self.openai_api_key = other.openai_api_key.clone();
+      self.openai_api_key_command = other.openai_api_key_command.clone();
+      self.openai_api_key_file = other.openai_api_key_file.clone();
+      self.openai_api_key_env = other.openai_api_key_env.clone();
+    }
+    if other.openai_base_url.is_some() {
+      self.openai_base_url = other.openai_base_url.clone();
+    }
+  }
+
+  /// Checks every value that is set before it is written to disk.
+  pub fn validate(&self) -> Result<()> {
+    if let Some(model) = &self.model {
+      if model.trim().is_empty() {
+        bail!("Model name cannot be empty");
+      }
+    }
+
+    if let Some(base_url) = self.openai_base_url.as_deref().map(str::trim) {
+      if !base_url.is_empty() {
+        let url = reqwest::Url::parse(base_url).with_context(|| format!("Invalid openai-base-url '{base_url}'"))?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+          bail!("Invalid openai-base-url '{base_url}': expected an http(s) URL such as http://localhost:11434/v1");
+        }
+      }
+    }
+
+    if let Some(max_tokens) = self.max_tokens {
+      let model: Model = self.model.as_deref().unwrap_or(DEFAULT_MODEL).into();
+      let context_size = model.context_size();
+      if max_tokens == 0 || max_tokens > context_size {
+        bail!(
+          "max-tokens must be between 1 and {context_size} (the context size of {model}), got {max_tokens}. \
+           Run `git ai config set max-tokens <n>` or `git ai config unset max-tokens`"
+        );
+      }
+    }
+
+    if let Some(max_commit_length) = self.max_commit_length {
+      if !(MIN_COMMIT_LENGTH..=MAX_COMMIT_LENGTH).contains(&max_commit_length) {
+        bail!("max-commit-length must be between {MIN_COMMIT_LENGTH} and {MAX_COMMIT_LENGTH}, got {max_commit_length}");
+      }
+    }
+
+    if let Some(timeout) = self.timeout {
+      if !(MIN_TIMEOUT..=MAX_TIMEOUT).contains(&timeout) {
+        bail!("timeout must be between {MIN_TIMEOUT} and {MAX_TIMEOUT} seconds, got {timeout}");
+      }
+    }
+
+    if let Some(pattern) = &self.branch_pattern {
+      crate::branch::validate_pattern(pattern)?;
+    }
+
+    if let Some(language) = &self.language {
+      crate::language::validate(language)?;
+    }
+
+    if let Some(profile) = &self.profile {
+      if !self.profiles.contains_key(profile) {
+        bail!("{}", unknown_profile(profile, &self.profiles));
+      }
+    }
+    for (name, profile) in &self.profiles {
+      validate_profile_name(name)?;
+      profile
+        .validate()
+        .with_context(|| format!("Invalid value in profile '{name}'"))?;
+    }
+
+    Ok(())
+  }
+
+  /// The contents of `config.toml` for these values, starting with the format version
+  fn to_toml(&self) -> Result<String> {
+    let body = toml::to_string(self).context("Failed to serialize config")?;
+    Ok(format!("version = {CONFIG_VERSION}\n{body}"))
+  }
+
   pub fn save(&self) -> Result<()> {
-    let contents = serde_ini::to_string(&self).context(format!("Failed to serialize config: {self:?}"))?;
+    self.validate()?;
+    let contents = self.to_toml()?;
     let mut file = File::create(&PATHS.file).with_context(|| format!("Failed to create config file at {:?}", PATHS.file))?;
     file
       .write_all(contents.as_bytes())
@@ -104,6 +652,7 @@ impl AppConfig {
   }
 
   pub fn update_openai_api_key(&mut self, value: String) -> Result<()> {
+    Self::check_plaintext_key_allowed()?;
     self.openai_api_key = Some(value);
     self.save_with_message("openai-api-key")
   }
@@ -113,9 +662,250 @@ impl AppConfig {
     self.save_with_message("openai-base-url")
   }
 
+  pub fn update_timeout(&mut self, value: usize) -> Result<()> {
+    self.timeout = Some(value);
+    self.save_with_message("timeout")
+  }
+
+  pub fn update_branch_pattern(&mut self, value: String) -> Result<()> {
+    self.branch_pattern = Some(value);
+    self.save_with_message("branch-pattern")
+  }
+
+  pub fn update_conventional_commits(&mut self, value: bool) -> Result<()> {
+    self.conventional_commits = Some(value);
+    self.save_with_message("conventional-commits")
+  }
+
+  /// Writes `key` to `scope` after checking the value. Profiles are created on first use;
+  /// repository values are checked with the rest of the configuration applied.
+  pub fn set(scope: &ConfigScope, key: ConfigKey, value: &str) -> Result<()> {
+    if key == ConfigKey::OpenaiApiKey {
+      Self::check_plaintext_key_allowed()?;
+    }
+
+    match scope {
+      ConfigScope::Global => {
+        let mut stored = Self::from_file()?;
+        stored.assign(key, value)?;
+        stored.save_with_message(key.name())
+      }
+      ConfigScope::Local => {
+        let mut merged = Self::new()?;
+        merged.assign(key, value)?;
+        merged.validate()?;
+
+        open_local_config()?
+          .set_str(&local_name(key), value)
+          .with_context(|| format!("Failed to write {} to .git/config", local_name(key)))?;
+        eprintln!("{} Configuration option {} updated for this repository!", Emoji("✨", ":-)"), key);
+        Ok(())
+      }
+      ConfigScope::Profile(name) => {
+        validate_profile_name(name)?;
+        let mut stored = Self::from_file()?;
+        stored
+          .profiles
+          .entry(name.clone())
+          .or_default()
+          .assign(key, value)?;
+        stored.save()?;
+        eprintln!("{} Configuration option {} updated in profile {}!", Emoji("✨", ":-)"), key, name);
+        Ok(())
+      }
+    }
+  }
+
+  /// Removes a single key from `scope` so the value of a lower layer applies again.
+  pub fn unset(scope: &ConfigScope, key: ConfigKey) -> Result<()> {
+    match scope {
+      ConfigScope::Global => {
+        let mut stored = Self::from_file()?;
+        stored.clear(key);
+        stored.save()?;
+        eprintln!("{} Configuration option {} unset!", Emoji("✨", ":-)"), key);
+      }
+      ConfigScope::Local => {
+        match open_local_config()?.remove(&local_name(key)) {
+          Ok(()) => {}
+          Err(e) if e.code() == git2::ErrorCode::NotFound => bail!("{key} is not set in this repository's .git/config"),
+          Err(e) => return Err(e).with_context(|| format!("Failed to remove {} from .git/config", local_name(key)))
+        }
+        eprintln!("{} Configuration option {} unset for this repository!", Emoji("✨", ":-)"), key);
+      }
+      ConfigScope::Profile(name) => {
+        let mut stored = Self::from_file()?;
+        let Some(profile) = stored.profiles.get_mut(name) else {
+          bail!("{}", unknown_profile(name, &stored.profiles));
+        };
+        profile.clear(key);
+        stored.save()?;
+        eprintln!("{} Configuration option {} unset in profile {}!", Emoji("✨", ":-)"), key, name);
+      }
+    }
+    Ok(())
+  }
+
+  /// Sets the field for `key` from its command-line representation
+  fn assign(&mut self, key: ConfigKey, value: &str) -> Result<()> {
+    let number = || {
+      value
+        .trim()
+        .parse::<usize>()
+        .with_context(|| format!("{key} must be a number, got '{value}'"))
+    };
+    let flag = || {
+      value
+        .trim()
+        .parse::<bool>()
+        .with_context(|| format!("{key} must be true or false, got '{value}'"))
+    };
+    match key {
+      ConfigKey::Model => self.model = Some(value.to_string()),
+      ConfigKey::MaxTokens => self.max_tokens = Some(number()?),
+      ConfigKey::MaxCommitLength => self.max_commit_length = Some(number()?),
+      ConfigKey::OpenaiApiKey => self.openai_api_key = Some(value.to_string()),
+      ConfigKey::OpenaiApiKeyCommand => self.openai_api_key_command = Some(value.to_string()),
+      ConfigKey::OpenaiApiKeyFile => self.openai_api_key_file = Some(value.to_string()),
+      ConfigKey::OpenaiApiKeyEnv => self.openai_api_key_env = Some(value.to_string()),
+      ConfigKey::ForbidPlaintextApiKey => self.forbid_plaintext_api_key = Some(flag()?),
+      ConfigKey::OpenaiBaseUrl => self.openai_base_url = Some(value.to_string()),
+      ConfigKey::Timeout => self.timeout = Some(number()?),
+      ConfigKey::BranchPattern => self.branch_pattern = Some(value.to_string()),
+      ConfigKey::ConventionalCommits => self.conventional_commits = Some(flag()?),
+      ConfigKey::Language => self.language = Some(value.trim().to_string())
+    }
+    Ok(())
+  }
+
+  fn clear(&mut self, key: ConfigKey) {
+    match key {
+      ConfigKey::Model => self.model = None,
+      ConfigKey::MaxTokens => self.max_tokens = None,
+      ConfigKey::MaxCommitLength => self.max_commit_length = None,
+      ConfigKey::OpenaiApiKey => self.openai_api_key = None,
+      ConfigKey::OpenaiApiKeyCommand => self.openai_api_key_command = None,
+      ConfigKey::OpenaiApiKeyFile => self.openai_api_key_file = None,
+      ConfigKey::OpenaiApiKeyEnv => self.openai_api_key_env = None,
+      ConfigKey::ForbidPlaintextApiKey => self.forbid_plaintext_api_key = None,
+      ConfigKey::OpenaiBaseUrl => self.openai_base_url = None,
+      ConfigKey::Timeout => self.timeout = None,
+      ConfigKey::BranchPattern => self.branch_pattern = None,
+      ConfigKey::ConventionalCommits => self.conventional_commits = None,
+      ConfigKey::Language => self.language = None
+    }
+  }
+
   fn save_with_message(&self, option: &str) -> Result<()> {
-    println!("{} Configuration option {} updated!", Emoji("✨", ":-)"), option);
-    self.save()
+    self.save()?;
+    eprintln!("{} Configuration option {} updated!", Emoji("✨", ":-)"), option);
+    Ok(())
+  }
+}
+
+fn env_source() -> config::Environment {
+  config::Environment::with_prefix("APP").try_parsing(true)
+}
+
+fn file_source() -> config::File<config::FileSourceFile, FileFormat> {
+  config::File::new(PATHS.file.to_string_lossy().as_ref(), FileFormat::Toml)
+}
+
+/// The name of `key` in `.git/config`, e.g. `ai.max-commit-length`
+fn local_name(key: ConfigKey) -> String {
+  format!("ai.{}", key.name())
+}
+
+/// The repository-level configuration of the repository git-ai runs in
+fn open_local_config() -> Result<git2::Config> {
+  let repo = git2::Repository::open_from_env().context("Not in a git repository; --local settings live in .git/config")?;
+  repo_config(&repo)
+}
+
+/// The `.git/config` of `repo`, without the global and system levels
+fn repo_config(repo: &git2::Repository) -> Result<git2::Config> {
+  repo
+    .config()?
+    .open_level(git2::ConfigLevel::Local)
+    .context("Failed to open the repository's .git/config")
+}
+
+/// The `ai.*` values in `config`. Keys git-ai does not know are skipped.
+fn local_values(config: &git2::Config) -> Result<Vec<(ConfigKey, String)>> {
+  let mut values = Vec::new();
+  let mut entries = config.entries(Some("^ai\\."))?;
+  while let Some(entry) = entries.next() {
+    let entry = entry?;
+    let (Ok(name), Ok(value)) = (entry.name(), entry.value()) else {
+      continue;
+    };
+    match name.trim_start_matches("ai.").parse::<ConfigKey>() {
+      Ok(key) => values.push((key, value.to_string())),
+      Err(_) => log::debug!("Ignoring unknown repository setting {name}")
+    }
+  }
+  Ok(values)
+}
+
+/// The `ai.*` keys of `repo`'s `.git/config` as a configuration layer, empty outside a
+/// repository
+fn local_source(repo: Option<&git2::Repository>) -> Result<Config> {
+  let mut builder = Config::builder();
+  if let Some(Ok(config)) = repo.map(repo_config) {
+    for (key, value) in local_values(&config)? {
+      builder = builder.set_override(key.field(), value)?;
+    }
+  }
+  Ok(builder.build()?)
+}
+
+fn profile_from_env() -> Option<String> {
+  std::env::var(PROFILE_ENV)
+    .ok()
+    .map(|name| name.trim().to_string())
+    .filter(|name| !name.is_empty())
+}
+
+fn validate_profile_name(name: &str) -> Result<()> {
+  if name.is_empty()
+    || !name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+  {
+    bail!("Invalid profile name '{name}': use letters, digits, '-' and '_'");
+  }
+  Ok(())
+}
+
+fn unknown_profile(name: &str, profiles: &BTreeMap<String, AppConfig>) -> String {
+  if profiles.is_empty() {
+    format!("Unknown profile '{name}'. Create it with `git ai config set --profile {name} <key> <value>`")
+  } else {
+    let known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+    format!("Unknown profile '{name}'. Known profiles: {}", known.join(", "))
+  }
+}
+
+/// The values of `profile` as a configuration layer
+fn profile_source(profile: Option<&AppConfig>) -> Result<Config> {
+  match profile {
+    Some(profile) => Ok(Config::try_from(profile)?),
+    None => Ok(Config::builder().build()?)
+  }
+}
+
+/// The first layer in `layers` that sets `key`, else its default
+fn resolve_entry(key: ConfigKey, layers: &[(ConfigSource, &Config)]) -> ConfigEntry {
+  for (source, layer) in layers {
+    if let Ok(value) = layer.get_string(key.field()) {
+      return ConfigEntry { key, value: Some(value), source: *source };
+    }
+  }
+
+  ConfigEntry {
+    key,
+    value: key.default_value(),
+    source: ConfigSource::Default
   }
 }
 
@@ -123,20 +913,29 @@ impl AppConfig {
 mod tests {
   use super::*;
 
-  /// F1: AppConfig round-trips `openai_base_url` through the INI serializer.
+  /// `config.toml` as `AppConfig::new` reads it, through the config crate
+  fn parse_toml(toml: &str) -> AppConfig {
+    Config::builder()
+      .add_source(config::File::from_str(toml, FileFormat::Toml))
+      .build()
+      .and_then(Config::try_deserialize)
+      .expect("deserialize")
+  }
+
+  /// F1: AppConfig round-trips `openai_base_url` through the TOML serializer.
   #[test]
-  fn test_openai_base_url_ini_round_trip() {
+  fn test_openai_base_url_toml_round_trip() {
     let config = AppConfig {
-      openai_api_key:    Some("sk-test".to_string()),
-      openai_base_url:   Some("http://localhost:11434/v1".to_string()),
-      model:             Some("gpt-4.1-mini".to_string()),
-      max_tokens:        Some(1024),
+      openai_api_key: Some("sk-test".to_string()),
+      openai_base_url: Some("http://localhost:11434/v1".to_string()),
+      model: Some("gpt-4.1-mini".to_string()),
+      max_tokens: Some(1024),
       max_commit_length: Some(72),
-      timeout:           Some(30)
+      timeout: Some(30),
+      ..Default::default()
     };
 
-    let ini = serde_ini::to_string(&config).expect("serialize");
-    let parsed: AppConfig = serde_ini::from_str(&ini).expect("deserialize");
+    let parsed = parse_toml(&config.to_toml().expect("serialize"));
     assert_eq!(parsed.openai_base_url, Some("http://localhost:11434/v1".to_string()));
     assert_eq!(parsed, config);
   }
@@ -145,16 +944,264 @@ mod tests {
   #[test]
   fn test_openai_base_url_absent_round_trip() {
     let config = AppConfig {
-      openai_api_key:    Some("sk-test".to_string()),
-      openai_base_url:   None,
-      model:             Some("gpt-4.1-mini".to_string()),
-      max_tokens:        Some(1024),
+      openai_api_key: Some("sk-test".to_string()),
+      openai_base_url: None,
+      model: Some("gpt-4.1-mini".to_string()),
+      max_tokens: Some(1024),
+      max_commit_length: Some(72),
+      timeout: Some(30),
+      ..Default::default()
+    };
+
+    let toml = config.to_toml().expect("serialize");
+    assert!(!toml.contains("openai_base_url"));
+    assert_eq!(parse_toml(&toml).openai_base_url, None);
+  }
+
+  #[test]
+  fn test_config_key_accepts_cli_and_field_names() {
+    assert_eq!("max-commit-length".parse::<ConfigKey>().unwrap(), ConfigKey::MaxCommitLength);
+    assert_eq!("openai_base_url".parse::<ConfigKey>().unwrap(), ConfigKey::OpenaiBaseUrl);
+    assert!("language-model".parse::<ConfigKey>().is_err());
+  }
+
+  #[test]
+  fn test_resolve_entry_prefers_file_over_environment() {
+    let file = Config::builder()
+      .set_override("model", "gpt-4.1")
+      .unwrap()
+      .build()
+      .unwrap();
+    let env = Config::builder()
+      .set_override("model", "gpt-4.1-nano")
+      .unwrap()
+      .set_override("max_tokens", 512)
+      .unwrap()
+      .build()
+      .unwrap();
+
+    let layers = [(ConfigSource::File, &file), (ConfigSource::Environment, &env)];
+
+    let model = resolve_entry(ConfigKey::Model, &layers);
+    assert_eq!(model.value.as_deref(), Some("gpt-4.1"));
+    assert_eq!(model.source, ConfigSource::File);
+
+    let max_tokens = resolve_entry(ConfigKey::MaxTokens, &layers);
+    assert_eq!(max_tokens.value.as_deref(), Some("512"));
+    assert_eq!(max_tokens.source, ConfigSource::Environment);
+
+    let base_url = resolve_entry(ConfigKey::OpenaiBaseUrl, &layers);
+    assert_eq!(base_url.value, None);
+    assert_eq!(base_url.source, ConfigSource::Default);
+  }
+
+  #[test]
+  fn test_local_values_win_over_the_global_file() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let mut git_config = repo.config().unwrap();
+    git_config.set_str("ai.max-commit-length", "100").unwrap();
+    git_config
+      .set_bool("ai.conventional-commits", true)
+      .unwrap();
+    git_config.set_str("ai.unknown-setting", "x").unwrap();
+
+    let values = local_values(&git_config.open_level(git2::ConfigLevel::Local).unwrap()).unwrap();
+    let mut keys: Vec<ConfigKey> = values.iter().map(|(key, _)| *key).collect();
+    keys.sort_by_key(|key| key.name());
+    assert_eq!(keys, vec![ConfigKey::ConventionalCommits, ConfigKey::MaxCommitLength]);
+
+    let mut local = Config::builder();
+    for (key, value) in values {
+      local = local.set_override(key.field(), value).unwrap();
+    }
+    let local = local.build().unwrap();
+    let file = Config::builder()
+      .set_override("max_commit_length", 50)
+      .unwrap()
+      .build()
+      .unwrap();
+    let max_commit_length = resolve_entry(ConfigKey::MaxCommitLength, &[(ConfigSource::Local, &local), (ConfigSource::File, &file)]);
+    assert_eq!(max_commit_length.value.as_deref(), Some("100"));
+    assert_eq!(max_commit_length.source, ConfigSource::Local);
+
+    let mut config = valid_config();
+    config
+      .assign(ConfigKey::MaxCommitLength, &max_commit_length.value.unwrap())
+      .unwrap();
+    assert_eq!(config.max_commit_length, Some(100));
+    assert!(config.assign(ConfigKey::Timeout, "soon").is_err());
+  }
+
+  #[test]
+  fn test_profiles_round_trip_as_toml_tables() {
+    let work = AppConfig {
+      openai_base_url: Some("https://work.openai.azure.com/v1".to_string()),
+      model: Some("gpt-4.1".to_string()),
+      ..Default::default()
+    };
+    let mut config = valid_config();
+    config.profile = Some("work".to_string());
+    config.profiles.insert("work".to_string(), work.clone());
+    config
+      .profiles
+      .insert("personal".to_string(), AppConfig { max_commit_length: Some(50), ..Default::default() });
+
+    let toml = config.to_toml().unwrap();
+    assert!(toml.contains("profile = \"work\""));
+    assert!(toml.contains("[profiles.work]"));
+
+    let file = Config::builder()
+      .add_source(config::File::from_str(&toml, FileFormat::Toml))
+      .build()
+      .unwrap();
+    let stored: AppConfig = file.clone().try_deserialize().unwrap();
+    assert_eq!(stored, config);
+    assert_eq!(stored.checked_profile().unwrap().as_deref(), Some("work"));
+
+    let profile = profile_source(Some(&work)).unwrap();
+    let layers = [(ConfigSource::Profile, &profile), (ConfigSource::File, &file)];
+    let model = resolve_entry(ConfigKey::Model, &layers);
+    assert_eq!((model.value.as_deref(), model.source), (Some("gpt-4.1"), ConfigSource::Profile));
+    let timeout = resolve_entry(ConfigKey::Timeout, &layers);
+    assert_eq!((timeout.value.as_deref(), timeout.source), (Some("30"), ConfigSource::File));
+
+    config.profile = Some("home".to_string());
+    assert!(config.validate().is_err());
+    assert!(validate_profile_name("work.eu").is_err());
+  }
+
+  #[test]
+  fn test_api_key_is_masked() {
+    let entry = ConfigEntry {
+      key:    ConfigKey::OpenaiApiKey,
+      value:  Some("sk-proj-abcdefghijklmnop".to_string()),
+      source: ConfigSource::File
+    };
+    assert_eq!(entry.display_value(), "sk-…mnop");
+
+    let placeholder = ConfigEntry { value: Some(DEFAULT_API_KEY.to_string()), ..entry };
+    assert_eq!(placeholder.display_value(), "(not set)");
+
+    let command = ConfigEntry {
+      key: ConfigKey::OpenaiApiKeyCommand,
+      value: Some("echo sk-proj-abcdefghijklmnop".to_string()),
+      ..placeholder
+    };
+    assert_eq!(command.display_value(), "echo …");
+    assert_eq!(mask_command("pass"), "pass");
+
+    let settings = AppConfig {
+      openai_api_key_command: command.value,
+      ..AppConfig::default()
+    };
+    assert!(!format!("{settings:?}").contains("abcdefghijklmnop"));
+  }
+
+  fn valid_config() -> AppConfig {
+    AppConfig {
+      openai_api_key: Some("sk-test".to_string()),
+      openai_base_url: Some("http://localhost:11434/v1".to_string()),
+      model: Some("gpt-4.1-mini".to_string()),
+      max_tokens: Some(1024),
       max_commit_length: Some(72),
-      timeout:           Some(30)
+      timeout: Some(30),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_validate_accepts_defaults_and_unset_values() {
+    assert!(valid_config().validate().is_ok());
+    assert!(AppConfig::default().validate().is_ok());
+  }
+
+  #[test]
+  fn test_validate_rejects_out_of_range_values() {
+    let base_url = AppConfig {
+      openai_base_url: Some("localhost:11434".to_string()),
+      ..valid_config()
+    };
+    assert!(base_url.validate().is_err());
+
+    let max_tokens = AppConfig { max_tokens: Some(10_000_000), ..valid_config() };
+    assert!(max_tokens.validate().is_err());
+
+    let max_commit_length = AppConfig { max_commit_length: Some(0), ..valid_config() };
+    assert!(max_commit_length.validate().is_err());
+
+    let timeout = AppConfig { timeout: Some(0), ..valid_config() };
+    assert!(timeout.validate().is_err());
+
+    let branch_pattern = AppConfig {
+      branch_pattern: Some("feature/<name>".to_string()),
+      ..valid_config()
     };
+    assert!(branch_pattern.validate().is_err());
+  }
+
+  #[test]
+  fn test_conventional_commits_toml_round_trip() {
+    let config = AppConfig { conventional_commits: Some(true), ..valid_config() };
+
+    let toml = config.to_toml().expect("serialize");
+    assert!(toml.contains("conventional_commits = true"));
+    assert_eq!(parse_toml(&toml), config);
+  }
+
+  #[test]
+  fn test_cleared_keys_are_omitted_from_toml() {
+    let mut config = valid_config();
+    config.clear(ConfigKey::MaxTokens);
+    config.clear(ConfigKey::Timeout);
+
+    let toml = config.to_toml().expect("serialize");
+    assert!(!toml.contains("max_tokens"));
+    assert!(!toml.contains("timeout"));
+    assert_eq!(parse_toml(&toml), config);
+  }
+
+  #[test]
+  fn test_config_ini_is_migrated_once_with_a_backup() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (ini, toml) = (dir.path().join("config.ini"), dir.path().join("config.toml"));
+    std::fs::write(
+      &ini,
+      "openai_api_key=sk-test\r\nmax_commit_length=50\r\nconventional_commits=true\r\nprofile=work\r\n\
+       [profiles.work]\r\nmodel=gpt-4.1\r\n"
+    )
+    .unwrap();
+
+    let backup = migrate(&ini, &toml).unwrap();
+    assert_eq!(backup, dir.path().join("config.ini.bak"));
+    assert!(!ini.exists() && backup.exists());
+
+    let contents = std::fs::read_to_string(&toml).unwrap();
+    assert!(contents.starts_with("version = 1\n"), "{contents}");
+    check_version(&toml).unwrap();
+
+    let migrated = parse_toml(&contents);
+    assert_eq!(migrated.openai_api_key.as_deref(), Some("sk-test"));
+    assert_eq!(migrated.max_commit_length, Some(50));
+    assert_eq!(migrated.conventional_commits, Some(true));
+    assert_eq!(migrated.profile.as_deref(), Some("work"));
+    assert_eq!(migrated.profiles["work"].model.as_deref(), Some("gpt-4.1"));
+  }
+
+  #[test]
+  fn test_newer_config_versions_are_refused() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+
+    std::fs::write(&path, "model = \"gpt-4.1\"\n").unwrap();
+    assert!(check_version(&path).is_ok());
+
+    std::fs::write(&path, "version = 2\nmodel = \"gpt-4.1\"\n").unwrap();
+    let error = check_version(&path).unwrap_err().to_string();
+    assert!(error.contains("is configuration version 2"), "{error}");
+    assert!(error.contains("only understands version 1"), "{error}");
 
-    let ini = serde_ini::to_string(&config).expect("serialize");
-    let parsed: AppConfig = serde_ini::from_str(&ini).expect("deserialize");
-    assert_eq!(parsed.openai_base_url, None);
+    std::fs::write(&path, "version = \"one\"\n").unwrap();
+    assert!(check_version(&path).is_err());
   }
 }
@@ -0,0 +1,382 @@
+use std::fmt;
+
+use async_openai::error::OpenAIError;
+use console::Emoji;
+
+use crate::api_key::KeySource;
+use crate::config::{self, AppConfig};
+use crate::filesystem::Filesystem;
+use crate::model::Model;
+use crate::openai;
+
+/// Outcome of a single diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+  Pass,
+  Warn,
+  Fail,
+  /// Not run because a check it depends on failed
+  Skip
+}
+
+/// A diagnostic result with an actionable fix when something is wrong
+#[derive(Debug, Clone)]
+pub struct Check {
+  pub name:   &'static str,
+  pub status: Status,
+  pub detail: String,
+  pub fix:    Option<String>
+}
+
+impl Check {
+  fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+    Check {
+      name,
+      status: Status::Pass,
+      detail: detail.into(),
+      fix: None
+    }
+  }
+
+  fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+    Check {
+      name,
+      status: Status::Warn,
+      detail: detail.into(),
+      fix: Some(fix.into())
+    }
+  }
+
+  fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+    Check {
+      name,
+      status: Status::Fail,
+      detail: detail.into(),
+      fix: Some(fix.into())
+    }
+  }
+
+  fn skip(name: &'static str, reason: impl Into<String>) -> Self {
+    Check {
+      name,
+      status: Status::Skip,
+      detail: reason.into(),
+      fix: None
+    }
+  }
+}
+
+impl fmt::Display for Check {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let icon = match self.status {
+      Status::Pass => Emoji("✅", "[ok]"),
+      Status::Warn => Emoji("⚠️ ", "[warn]"),
+      Status::Fail => Emoji("❌", "[fail]"),
+      Status::Skip => Emoji("⏭️ ", "[skip]")
+    };
+    write!(f, "{icon} {}: {}", self.name, self.detail)?;
+    if let Some(fix) = &self.fix {
+      write!(f, "\n   → {fix}")?;
+    }
+    Ok(())
+  }
+}
+
+/// Runs every diagnostic in order. Checks that need a working configuration, key or
+/// endpoint are skipped when those are missing, so the first failure is the one to fix.
+pub async fn run() -> Vec<Check> {
+  let mut checks = vec![check_hook()];
+
+  let (check, settings) = check_config();
+  checks.push(check);
+  let Some(settings) = settings else {
+    checks.push(Check::skip("API key", "configuration did not load"));
+    checks.push(Check::skip("Endpoint", "configuration did not load"));
+    checks.push(Check::skip("Model", "configuration did not load"));
+    checks.push(Check::skip("Tool calls", "configuration did not load"));
+    return checks;
+  };
+
+  let (check, settings) = check_api_key(settings);
+  checks.push(check);
+  let Some(settings) = settings else {
+    checks.push(Check::skip("Endpoint", "no usable API key"));
+    checks.push(Check::skip("Model", "no usable API key"));
+    checks.push(Check::skip("Tool calls", "no usable API key"));
+    return checks;
+  };
+
+  let check = check_endpoint(&settings).await;
+  let reachable = check.status == Status::Pass;
+  checks.push(check);
+  if !reachable {
+    checks.push(Check::skip("Model", "endpoint not reachable"));
+    checks.push(Check::skip("Tool calls", "endpoint not reachable"));
+    return checks;
+  }
+
+  let model = Model::from(
+    settings
+      .model
+      .clone()
+      .unwrap_or(config::DEFAULT_MODEL.to_string())
+  )
+  .to_string();
+  let check = check_model(&settings, &model).await;
+  let model_found = check.status == Status::Pass;
+  checks.push(check);
+  if !model_found {
+    checks.push(Check::skip("Tool calls", "model not available"));
+    return checks;
+  }
+
+  checks.push(check_tool_calls(&settings, &model).await);
+  checks
+}
+
+fn check_hook() -> Check {
+  const NAME: &str = "Hook";
+
+  let fs = match Filesystem::new() {
+    Ok(fs) => fs,
+    Err(e) =>
+      return Check::fail(
+        NAME,
+        e.to_string(),
+        "Run git-ai inside a repository, next to an installed git-ai-hook binary"
+      ),
+  };
+  let (hook_file, hook_bin) = match (fs.prepare_commit_msg_path(), fs.git_ai_hook_bin_path()) {
+    (Ok(file), Ok(bin)) => (file, bin),
+    (Err(e), _) | (_, Err(e)) => return Check::fail(NAME, e.to_string(), "Run `git ai hook install`")
+  };
+
+  if !hook_file.exists() {
+    return Check::fail(NAME, format!("no prepare-commit-msg hook at {hook_file}"), "Run `git ai hook install`");
+  }
+
+  if hook_file.points_to(&hook_bin) {
+    return Check::pass(NAME, format!("{hook_file} links to the current git-ai-hook"));
+  }
+
+  match hook_file.link_target() {
+    Some(target) =>
+      Check::fail(
+        NAME,
+        format!("{hook_file} points to {}, not the current git-ai-hook", target.display()),
+        "Run `git ai hook reinstall`"
+      ),
+    None =>
+      Check::fail(
+        NAME,
+        format!("{hook_file} is a regular file, not the git-ai hook"),
+        "Move the existing hook out of the way, then run `git ai hook install`"
+      ),
+  }
+}
+
+fn check_config() -> (Check, Option<AppConfig>) {
+  const NAME: &str = "Config";
+  let path = config::config_file_path();
+
+  match AppConfig::new() {
+    Ok(settings) =>
+      match settings.validate() {
+        Ok(()) => (Check::pass(NAME, format!("{} parsed", path.display())), Some(settings)),
+        Err(e) =>
+          (
+            Check::warn(
+              NAME,
+              format!("{} parsed but {e}", path.display()),
+              "Fix the value with `git ai config set` or `git ai config unset`"
+            ),
+            Some(settings)
+          ),
+      },
+    Err(e) =>
+      (
+        Check::fail(
+          NAME,
+          format!("{} does not parse: {e:#}", path.display()),
+          "Fix the file by hand or run `git ai config reset`"
+        ),
+        None
+      ),
+  }
+}
+
+/// Falls back to `OPENAI_API_KEY` the same way generation does when the configured key
+/// is missing, and returns the settings with the key that will actually be used.
+fn check_api_key(mut settings: AppConfig) -> (Check, Option<AppConfig>) {
+  const NAME: &str = "API key";
+
+  match settings.api_key() {
+    Ok(Some(key)) => {
+      let detail = match KeySource::of(&settings) {
+        Some(source) => format!("from {source} ({})", config::mask_secret(&key)),
+        None => format!("configured ({})", config::mask_secret(&key))
+      };
+      return (Check::pass(NAME, detail), Some(settings));
+    }
+    Ok(None) => {}
+    Err(e) =>
+      return (
+        Check::fail(
+          NAME,
+          format!("{e:#}"),
+          "Fix the key source or choose another with `git ai config set openai-api-key-command <command>`"
+        ),
+        None
+      ),
+  }
+
+  if openai::create_openai_config(&settings).is_ok() {
+    return (Check::pass(NAME, "not needed for the custom base URL"), Some(settings));
+  }
+
+  match std::env::var("OPENAI_API_KEY") {
+    Ok(key) if !key.is_empty() => {
+      let detail = format!("from OPENAI_API_KEY ({})", config::mask_secret(&key));
+      settings.openai_api_key = Some(key);
+      (Check::pass(NAME, detail), Some(settings))
+    }
+    _ =>
+      (
+        Check::fail(
+          NAME,
+          "no API key configured, so commit messages cannot be generated",
+          "Run `git ai config set openai-api-key <key>` or export OPENAI_API_KEY"
+        ),
+        None
+      ),
+  }
+}
+
+fn endpoint_name(settings: &AppConfig) -> String {
+  settings
+    .openai_base_url
+    .clone()
+    .filter(|url| !url.trim().is_empty())
+    .unwrap_or_else(|| "https://api.openai.com/v1".to_string())
+}
+
+async fn check_endpoint(settings: &AppConfig) -> Check {
+  const NAME: &str = "Endpoint";
+  let endpoint = endpoint_name(settings);
+
+  match openai::list_models(settings).await {
+    Ok(models) => Check::pass(NAME, format!("{endpoint} reachable ({} models)", models.len())),
+    Err(e) =>
+      match e.downcast_ref::<OpenAIError>() {
+        Some(OpenAIError::ApiError(api)) if api.status_code.as_u16() == 401 || api.api_error.code.as_deref() == Some("invalid_api_key") =>
+          Check::fail(
+            NAME,
+            format!("{endpoint} rejected the API key"),
+            "Run `git ai config set openai-api-key <key>` with a valid key"
+          ),
+        Some(OpenAIError::ApiError(api)) =>
+          Check::fail(
+            NAME,
+            format!("{endpoint} answered with an error: {api}"),
+            "Check the endpoint's status and your account"
+          ),
+        _ =>
+          Check::fail(
+            NAME,
+            format!("{endpoint} is not reachable: {e}"),
+            "Check your network, or point `git ai config set openai-base-url <url>` at a running endpoint"
+          ),
+      },
+  }
+}
+
+async fn check_model(settings: &AppConfig, model: &str) -> Check {
+  const NAME: &str = "Model";
+
+  // Ask the endpoint even for built-in models, a custom endpoint may not serve them
+  match openai::verify_model_exists(settings, model, false).await {
+    Ok(()) => Check::pass(NAME, format!("{model} is available")),
+    Err(e) =>
+      Check::fail(
+        NAME,
+        e.to_string(),
+        "Run `git ai models` and pick one with `git ai config set model <name>`"
+      ),
+  }
+}
+
+async fn check_tool_calls(settings: &AppConfig, model: &str) -> Check {
+  const NAME: &str = "Tool calls";
+
+  match openai::supports_forced_tool_calls(settings, model).await {
+    Ok(true) => Check::pass(NAME, format!("{model} honors forced tool calls")),
+    Ok(false) =>
+      Check::fail(
+        NAME,
+        format!("{model} ignored a forced tool call; multi-step generation will fall back to the local generator"),
+        "Use a model with function calling support, e.g. `git ai config set model gpt-4.1-mini`"
+      ),
+    Err(e) =>
+      Check::fail(
+        NAME,
+        format!("{model} rejected a forced tool call: {e}"),
+        "Use a model with function calling support, e.g. `git ai config set model gpt-4.1-mini`"
+      ),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_api_key_check_masks_configured_key() {
+    let settings = AppConfig {
+      openai_api_key: Some("sk-abcdefghijklmnop".to_string()),
+      ..Default::default()
+    };
+
+    let (check, settings) = check_api_key(settings);
+    assert_eq!(check.status, Status::Pass);
+    assert_eq!(check.detail, "configured (sk-…mnop)");
+    assert!(settings.is_some());
+  }
+
+  #[test]
+  fn test_api_key_check_reports_key_source() {
+    let settings = AppConfig {
+      openai_api_key: Some("sk-stored-but-unused".to_string()),
+      openai_api_key_command: Some("echo sk-abcdefghijklmnop".to_string()),
+      ..Default::default()
+    };
+    let (check, _) = check_api_key(settings);
+    assert_eq!(check.detail, "from openai-api-key-command `echo …` (sk-…mnop)");
+
+    let settings = AppConfig {
+      openai_api_key_env: Some("GIT_AI_TEST_UNSET_KEY".to_string()),
+      ..Default::default()
+    };
+    let (check, settings) = check_api_key(settings);
+    assert_eq!(check.status, Status::Fail);
+    assert_eq!(
+      check.detail,
+      "openai-api-key-env $GIT_AI_TEST_UNSET_KEY is not set: environment variable not found"
+    );
+    assert!(settings.is_none());
+  }
+
+  #[test]
+  fn test_custom_endpoint_needs_no_key() {
+    let settings = AppConfig {
+      openai_base_url: Some("http://localhost:11434/v1".to_string()),
+      ..Default::default()
+    };
+
+    let (check, _) = check_api_key(settings);
+    assert_eq!(check.status, Status::Pass);
+    assert_eq!(
+      check.to_string(),
+      format!("{} API key: not needed for the custom base URL", Emoji("✅", "[ok]"))
+    );
+  }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use anyhow::{Context, Result};
+use git2::{Commit, Repository};
+use serde::Serialize;
+
+use crate::commit;
+use crate::config::AppConfig;
+use crate::generation::{CommitResponse, FileChange};
+use crate::hook::{PatchDiff, PatchRepository, Utf8String};
+use crate::model::Model;
+
+/// A plain-language explanation of an existing commit
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+  /// The full object id of the explained commit
+  pub commit:   String,
+  /// The subject line the commit was originally written with
+  pub subject:  String,
+  pub author:   String,
+  /// What the multi-step analysis made of the commit's changes
+  #[serde(flatten)]
+  pub response: CommitResponse
+}
+
+impl Explanation {
+  /// The analyzed files, most impactful first
+  pub fn files_by_impact(&self) -> Vec<&FileChange> {
+    let mut files: Vec<&FileChange> = self.response.files.values().collect();
+    files.sort_by(|a, b| {
+      b.impact_score
+        .partial_cmp(&a.impact_score)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then_with(|| a.file_path.cmp(&b.file_path))
+    });
+    files
+  }
+}
+
+impl fmt::Display for Explanation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let short_id = self.commit.get(..7).unwrap_or(&self.commit);
+    writeln!(f, "{short_id} {}", self.subject)?;
+    writeln!(f, "Author: {}", self.author)?;
+    writeln!(f)?;
+    writeln!(f, "{}", self.response.message.trim())?;
+
+    let reasoning = self.response.reasoning.trim();
+    if !reasoning.is_empty() {
+      writeln!(f)?;
+      writeln!(f, "{reasoning}")?;
+    }
+
+    let files = self.files_by_impact();
+    if !files.is_empty() {
+      writeln!(f)?;
+      writeln!(f, "Files:")?;
+      for file in files {
+        writeln!(
+          f,
+          "  {:<9} {} (+{} -{}): {}",
+          file.operation_type.as_str(),
+          file.file_path,
+          file.lines_added,
+          file.lines_removed,
+          file.summary
+        )?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// Builds the patch introduced by `commit`, i.e. its diff against the first parent
+/// (or against an empty tree for a root commit).
+pub fn commit_patch(repo: &Repository, commit: &Commit<'_>, max_tokens: usize, model: Model) -> Result<String> {
+  let parent_tree = match commit.parent(0) {
+    Ok(parent) => Some(parent.tree()?),
+    Err(_) => None
+  };
+
+  repo
+    .to_tree_diff(parent_tree, &commit.tree()?)?
+    .to_patch(max_tokens, model)
+}
+
+/// Explains the commit `rev` resolves to by running its diff through the same
+/// multi-step analysis used for new commits.
+pub async fn explain(repo: &Repository, rev: &str, model: Model, settings: Option<&AppConfig>) -> Result<Explanation> {
+  let commit = repo
+    .revparse_single(rev)
+    .and_then(|object| object.peel_to_commit())
+    .with_context(|| format!("Failed to resolve '{rev}' to a commit"))?;
+
+  let remaining_tokens = match settings {
+    Some(settings) => commit::remaining_tokens_for(&model, settings)?,
+    None => commit::remaining_tokens(&model)?
+  };
+  let patch = commit_patch(repo, &commit, remaining_tokens, model.clone())?;
+  if patch.trim().is_empty() {
+    anyhow::bail!("Commit {} has no changes to explain", commit.id());
+  }
+
+  let response = commit::generate_response(patch, remaining_tokens, model, settings).await?;
+  let author = commit.author();
+
+  Ok(Explanation {
+    commit: commit.id().to_string(),
+    subject: commit.summary_bytes().unwrap_or_default().to_utf8(),
+    author: format!("{} <{}>", author.name_bytes().to_utf8(), author.email_bytes().to_utf8()),
+    response
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use tempfile::TempDir;
+
+  use super::*;
+
+  fn commit_file(repo: &Repository, dir: &Path, name: &str, content: &str, message: &str) -> git2::Oid {
+    std::fs::write(dir.join(name), content).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(name)).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    let parents: Vec<Commit> = repo
+      .head()
+      .ok()
+      .and_then(|head| head.peel_to_commit().ok())
+      .into_iter()
+      .collect();
+    let parents: Vec<&Commit> = parents.iter().collect();
+    repo
+      .commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+      .unwrap()
+  }
+
+  #[test]
+  fn test_commit_patch_is_diff_against_first_parent() {
+    let dir = TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+
+    let root = commit_file(&repo, dir.path(), "a.txt", "one\n", "Add a");
+    let second = commit_file(&repo, dir.path(), "b.txt", "two\n", "Add b");
+
+    let root_patch = commit_patch(&repo, &repo.find_commit(root).unwrap(), 4096, Model::GPT41Mini).unwrap();
+    assert!(root_patch.contains("a.txt"));
+    assert!(!root_patch.contains("b.txt"));
+
+    let second_patch = commit_patch(&repo, &repo.find_commit(second).unwrap(), 4096, Model::GPT41Mini).unwrap();
+    assert!(second_patch.contains("b.txt"));
+    assert!(second_patch.contains("+two"));
+    assert!(!second_patch.contains("a.txt"));
+  }
+}
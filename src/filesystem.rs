@@ -61,6 +61,28 @@ impl File {
     symlink_unix(&target.path, &self.path).with_context(|| format!("Failed to symlink {target} to {self}"))
   }
 
+  /// Checks if the file resolves to the same file as `target`, following symlinks.
+  ///
+  /// # Arguments
+  /// * `target` - The file the link is expected to point at
+  ///
+  /// # Returns
+  /// * `bool` - true if both resolve to the same path, false otherwise
+  pub fn points_to(&self, target: &File) -> bool {
+    match (fs::canonicalize(&self.path), fs::canonicalize(&target.path)) {
+      (Ok(resolved), Ok(expected)) => resolved == expected,
+      _ => false
+    }
+  }
+
+  /// Reads the target of the file if it is a symbolic link.
+  ///
+  /// # Returns
+  /// * `Option<PathBuf>` - The link target, or None if the file is not a symlink
+  pub fn link_target(&self) -> Option<PathBuf> {
+    fs::read_link(&self.path).ok()
+  }
+
   /// Gets the relative path from the current directory.
   ///
   /// # Returns
@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use git2::{Blame, BlameOptions, Commit, Oid, Repository};
+
+use crate::commit;
+use crate::hook::{PatchDiff, PatchRepository, Utf8String};
+use crate::model::Model;
+use crate::split::{build_trees, collect_hunks, staged_diff, Group, Hunk, PlannedCommit};
+
+/// Group name for the hunks no unpushed commit could be found for
+const UNATTRIBUTED: &str = "unattributed";
+
+/// One commit of the fixup series
+#[derive(Debug, Clone)]
+pub struct PlannedFixup {
+  /// The commit the hunks amend, `None` for the hunks that could not be attributed
+  pub target: Option<Oid>,
+  pub commit: PlannedCommit
+}
+
+/// The non-merge commits on `HEAD` that no remote-tracking branch contains, oldest
+/// first. Without remote-tracking branches every commit counts as unpushed.
+pub fn unpushed_commits(repo: &Repository) -> Result<Vec<Commit<'_>>> {
+  let mut revwalk = repo.revwalk()?;
+  revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+  revwalk.push_head()?;
+  revwalk.hide_glob("refs/remotes/*")?;
+
+  let mut commits = Vec::new();
+  for oid in revwalk {
+    let commit = repo.find_commit(oid?)?;
+    if commit.parent_count() <= 1 {
+      commits.push(commit);
+    }
+  }
+
+  Ok(commits)
+}
+
+/// The commit that last touched every line of `lines` in `blame`, if it is a single one.
+/// Lines past the end of the file are ignored.
+fn last_touched_by(blame: &Blame<'_>, (first, last): (u32, u32)) -> Option<Oid> {
+  let mut commits = (first..=last)
+    .filter_map(|line| blame.get_line(line as usize))
+    .map(|hunk| hunk.final_commit_id());
+  let commit = commits.next()?;
+  commits.all(|other| other == commit).then_some(commit)
+}
+
+/// Blames the lines each hunk touches in `HEAD` and returns the unpushed commit that last
+/// touched all of them. Hunks touching lines from several commits, pushed commits or
+/// files that are new are not attributed.
+pub fn attribute(repo: &Repository, hunks: &[Hunk], unpushed: &HashSet<Oid>) -> Result<Vec<Option<Oid>>> {
+  let mut blames: HashMap<PathBuf, Option<Blame<'_>>> = HashMap::new();
+  let mut targets = Vec::with_capacity(hunks.len());
+
+  for hunk in hunks {
+    let Some(lines) = hunk.old_lines else {
+      targets.push(None);
+      continue;
+    };
+
+    let blame = blames.entry(hunk.path.clone()).or_insert_with(|| {
+      repo
+        .blame_file(&hunk.path, Some(&mut BlameOptions::new()))
+        .ok()
+    });
+    let target = blame
+      .as_ref()
+      .and_then(|blame| last_touched_by(blame, lines))
+      .filter(|commit| unpushed.contains(commit));
+    targets.push(target);
+  }
+
+  Ok(targets)
+}
+
+/// Routes the staged hunks to the unpushed commits they amend: one `fixup!` commit per
+/// target in stack order, then a commit with a generated message for the hunks that
+/// could not be attributed.
+pub async fn plan(repo: &Repository, model: Model) -> Result<Vec<PlannedFixup>> {
+  let (head, diff) = staged_diff(repo)?;
+  if diff.is_empty()? {
+    bail!("No staged changes to route to fixups");
+  }
+
+  let unpushed = unpushed_commits(repo)?;
+  let order: HashMap<Oid, usize> = unpushed
+    .iter()
+    .enumerate()
+    .map(|(i, commit)| (commit.id(), i))
+    .collect();
+  let hunks = collect_hunks(&diff)?;
+  let targets = attribute(repo, &hunks, &order.keys().copied().collect())?;
+
+  // Keyed by position in the stack, unattributed hunks last
+  let mut grouped: BTreeMap<usize, (Option<Oid>, Vec<Hunk>)> = BTreeMap::new();
+  for (hunk, target) in hunks.into_iter().zip(targets) {
+    let key = target.map_or(usize::MAX, |target| order[&target]);
+    grouped
+      .entry(key)
+      .or_insert((target, Vec::new()))
+      .1
+      .push(hunk);
+  }
+
+  let (targets, groups): (Vec<Option<Oid>>, Vec<Group>) = grouped
+    .into_values()
+    .map(|(target, hunks)| {
+      let name = target.map_or(UNATTRIBUTED.to_string(), |target| format!("{target:.7}"));
+      (target, Group { name, hunks })
+    })
+    .unzip();
+  let trees = build_trees(repo, head.as_ref(), &diff, &groups)?;
+
+  let mut previous = head;
+  let mut planned = Vec::with_capacity(groups.len());
+  for ((target, group), tree_id) in targets.into_iter().zip(groups).zip(trees) {
+    let tree = repo.find_tree(tree_id)?;
+    let message = match target {
+      Some(target) => {
+        let summary = repo
+          .find_commit(target)?
+          .summary_bytes()
+          .unwrap_or_default()
+          .to_utf8();
+        format!("fixup! {summary}")
+      }
+      None => {
+        let remaining_tokens = commit::remaining_tokens(&model)?;
+        let patch = repo
+          .to_tree_diff(previous, &tree)?
+          .to_patch(remaining_tokens, model.clone())?;
+        commit::generate(patch, remaining_tokens, model.clone(), None)
+          .await?
+          .response
+          .trim()
+          .to_string()
+      }
+    };
+
+    planned.push(PlannedFixup {
+      target,
+      commit: PlannedCommit { group, tree: tree_id, message }
+    });
+    previous = Some(tree);
+  }
+
+  Ok(planned)
+}
+
+/// The revision to pass to `git rebase -i --autosquash` so every target is folded in:
+/// the parent of the oldest target, `None` when that is the root commit.
+pub fn rebase_base(repo: &Repository, planned: &[PlannedFixup]) -> Result<Option<Oid>> {
+  let targets: HashSet<Oid> = planned.iter().filter_map(|fixup| fixup.target).collect();
+  let oldest = unpushed_commits(repo)?
+    .into_iter()
+    .find(|commit| targets.contains(&commit.id()));
+  Ok(oldest.and_then(|commit| commit.parent_id(0).ok()))
+}
@@ -1,3 +1,3 @@
 pub mod types;
 
-pub use types::{CommitResponse, FileCategory, FileChange, OperationType};
+pub use types::{CommitResponse, FileCategory, FileChange, OperationType, Strategy};
@@ -2,10 +2,13 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use crate::multi_step_analysis::FileWithScore;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileChange {
   pub file_path:      String,
   pub operation_type: OperationType,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
   pub diff_content:   Option<String>,
   pub lines_added:    u32,
   pub lines_removed:  u32,
@@ -85,10 +88,65 @@ impl From<&str> for FileCategory {
   }
 }
 
+impl From<&FileWithScore> for FileChange {
+  fn from(file: &FileWithScore) -> Self {
+    FileChange {
+      file_path:      file.file_path.clone(),
+      operation_type: file.operation_type.as_str().into(),
+      diff_content:   None,
+      lines_added:    file.lines_added,
+      lines_removed:  file.lines_removed,
+      file_category:  file.file_category.as_str().into(),
+      summary:        file.summary.clone(),
+      impact_score:   file.impact_score
+    }
+  }
+}
+
+/// Which generation path produced a commit message
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+  /// The OpenAI multi-step pipeline (analyze, score, generate, select)
+  MultiStep,
+  /// The local, programmatic multi-step analysis (no API calls)
+  Local,
+  /// The original single-step OpenAI request
+  SingleStep
+}
+
+impl Strategy {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Strategy::MultiStep => "multi-step",
+      Strategy::Local => "local",
+      Strategy::SingleStep => "single-step"
+    }
+  }
+}
+
 /// Unified response type for commit message generation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommitResponse {
-  pub message:   String,
-  pub reasoning: String,
-  pub files:     HashMap<String, FileChange>
+  pub message:    String,
+  pub reasoning:  String,
+  pub files:      HashMap<String, FileChange>,
+  /// Every candidate considered before the final message was selected
+  #[serde(default)]
+  pub candidates: Vec<String>,
+  pub strategy:   Strategy
+}
+
+impl CommitResponse {
+  /// Builds a response from the scored files of a multi-step run (API or local).
+  pub fn from_scored_files(
+    message: String, reasoning: String, candidates: Vec<String>, files: &[FileWithScore], strategy: Strategy
+  ) -> Self {
+    let files = files
+      .iter()
+      .map(|file| (file.file_path.clone(), FileChange::from(file)))
+      .collect();
+
+    CommitResponse { message, reasoning, files, candidates, strategy }
+  }
 }
@@ -65,7 +65,7 @@ impl FilePath for PathBuf {
 }
 
 // Git operations traits
-trait DiffDeltaPath {
+pub trait DiffDeltaPath {
   fn path(&self) -> PathBuf;
 }
 
@@ -112,6 +112,14 @@ pub trait PatchDiff {
   fn to_patch(&self, max_token_count: usize, model: Model) -> Result<String>;
   fn collect_diff_data(&self) -> Result<HashMap<PathBuf, String>>;
   fn is_empty(&self) -> Result<bool>;
+
+  /// The complete patch with every file in path order and nothing truncated, for
+  /// local analysis that does not have to fit a token budget
+  fn to_full_patch(&self) -> Result<String> {
+    let mut files: Vec<(PathBuf, String)> = self.collect_diff_data()?.into_iter().collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files.into_iter().map(|(_, content)| content).collect())
+  }
 }
 
 impl PatchDiff for Diff<'_> {
@@ -434,6 +442,7 @@ pub trait PatchRepository {
   fn to_patch(&self, tree: Option<Tree<'_>>, max_token_count: usize, model: Model) -> Result<String>;
   fn to_diff(&self, tree: Option<Tree<'_>>) -> Result<git2::Diff<'_>>;
   fn to_commit_diff(&self, tree: Option<Tree<'_>>) -> Result<git2::Diff<'_>>;
+  fn to_tree_diff(&self, old_tree: Option<Tree<'_>>, new_tree: &Tree<'_>) -> Result<git2::Diff<'_>>;
   fn configure_diff_options(&self, opts: &mut DiffOptions);
   fn configure_commit_diff_options(&self, opts: &mut DiffOptions);
 }
@@ -484,6 +493,25 @@ impl PatchRepository for Repository {
     .context("Failed to get diff")
   }
 
+  fn to_tree_diff(&self, old_tree: Option<Tree<'_>>, new_tree: &Tree<'_>) -> Result<git2::Diff<'_>> {
+    profile!("Git tree diff generation");
+    let mut opts = DiffOptions::new();
+    self.configure_commit_diff_options(&mut opts);
+
+    match old_tree {
+      Some(tree) => {
+        // Get the diff between two committed trees (e.g. a commit and its first parent)
+        self.diff_tree_to_tree(Some(&tree), Some(new_tree), Some(&mut opts))
+      }
+      None => {
+        // A root commit has no parent, so compare against an empty tree
+        let empty_tree = self.find_tree(self.treebuilder(None)?.write()?)?;
+        self.diff_tree_to_tree(Some(&empty_tree), Some(new_tree), Some(&mut opts))
+      }
+    }
+    .context("Failed to get diff")
+  }
+
   fn configure_diff_options(&self, opts: &mut DiffOptions) {
     opts
       .ignore_whitespace_change(true)
@@ -0,0 +1,206 @@
+use anyhow::{bail, Result};
+
+/// Language codes and the English names the prompts use for them
+const LANGUAGES: [(&str, &str); 16] = [
+  ("en", "English"),
+  ("sv", "Swedish"),
+  ("de", "German"),
+  ("fr", "French"),
+  ("es", "Spanish"),
+  ("nb", "Norwegian"),
+  ("no", "Norwegian"),
+  ("da", "Danish"),
+  ("fi", "Finnish"),
+  ("nl", "Dutch"),
+  ("it", "Italian"),
+  ("pt", "Portuguese"),
+  ("pl", "Polish"),
+  ("ja", "Japanese"),
+  ("zh", "Chinese"),
+  ("ko", "Korean")
+];
+
+/// Wording of the subjects the local analysis writes when the API is not used.
+/// `{}` stands for the component, e.g. `auth` for `src/auth.rs`.
+#[derive(Debug)]
+pub(crate) struct Templates {
+  pub add:                  &'static str,
+  pub update:               &'static str,
+  pub remove:               &'static str,
+  pub rename:               &'static str,
+  pub change:               &'static str,
+  pub implementation:       &'static str,
+  pub updates:              &'static str,
+  pub removal:              &'static str,
+  pub changes:              &'static str,
+  pub new_feature:          &'static str,
+  pub update_tests:         &'static str,
+  pub update_configuration: &'static str,
+  pub update_other:         &'static str
+}
+
+const ENGLISH: Templates = Templates {
+  add:                  "Add {}",
+  update:               "Update {}",
+  remove:               "Remove {}",
+  rename:               "Rename {}",
+  change:               "Change {}",
+  implementation:       "implementation",
+  updates:              "updates",
+  removal:              "removal",
+  changes:              "changes",
+  new_feature:          "New feature for {}",
+  update_tests:         "Update test for {}",
+  update_configuration: "Update configuration for {}",
+  update_other:         "Update update for {}"
+};
+
+const SWEDISH: Templates = Templates {
+  add:                  "Lägg till {}",
+  update:               "Uppdatera {}",
+  remove:               "Ta bort {}",
+  rename:               "Byt namn på {}",
+  change:               "Ändra {}",
+  implementation:       "implementation",
+  updates:              "uppdateringar",
+  removal:              "borttagning",
+  changes:              "ändringar",
+  new_feature:          "Ny funktion för {}",
+  update_tests:         "Uppdatera tester för {}",
+  update_configuration: "Uppdatera konfiguration för {}",
+  update_other:         "Uppdatera {}"
+};
+
+const GERMAN: Templates = Templates {
+  add:                  "{} hinzufügen",
+  update:               "{} aktualisieren",
+  remove:               "{} entfernen",
+  rename:               "{} umbenennen",
+  change:               "{} ändern",
+  implementation:       "Implementierung",
+  updates:              "Aktualisierungen",
+  removal:              "Entfernung",
+  changes:              "Änderungen",
+  new_feature:          "Neue Funktion für {}",
+  update_tests:         "Tests für {} aktualisieren",
+  update_configuration: "Konfiguration für {} aktualisieren",
+  update_other:         "{} aktualisieren"
+};
+
+const FRENCH: Templates = Templates {
+  add:                  "Ajouter {}",
+  update:               "Mettre à jour {}",
+  remove:               "Supprimer {}",
+  rename:               "Renommer {}",
+  change:               "Modifier {}",
+  implementation:       "implémentation",
+  updates:              "mises à jour",
+  removal:              "suppression",
+  changes:              "modifications",
+  new_feature:          "Nouvelle fonctionnalité pour {}",
+  update_tests:         "Mettre à jour les tests de {}",
+  update_configuration: "Mettre à jour la configuration de {}",
+  update_other:         "Mettre à jour {}"
+};
+
+const SPANISH: Templates = Templates {
+  add:                  "Añadir {}",
+  update:               "Actualizar {}",
+  remove:               "Eliminar {}",
+  rename:               "Renombrar {}",
+  change:               "Cambiar {}",
+  implementation:       "implementación",
+  updates:              "actualizaciones",
+  removal:              "eliminación",
+  changes:              "cambios",
+  new_feature:          "Nueva funcionalidad para {}",
+  update_tests:         "Actualizar pruebas de {}",
+  update_configuration: "Actualizar configuración de {}",
+  update_other:         "Actualizar {}"
+};
+
+/// `template` with the component filled in
+pub(crate) fn fill(template: &str, component: &str) -> String {
+  template.replace("{}", component)
+}
+
+/// The English name of `language`, which may be a code (`sv`, `sv-SE`) or a name
+/// (`swedish`). Languages missing from the table are passed on as written.
+pub fn name(language: &str) -> String {
+  let language = language.trim();
+  let code = language
+    .split(['-', '_'])
+    .next()
+    .unwrap_or_default()
+    .to_lowercase();
+  LANGUAGES
+    .iter()
+    .find(|(known, name)| *known == code || name.eq_ignore_ascii_case(language))
+    .map_or(language.to_string(), |(_, name)| name.to_string())
+}
+
+/// The instruction added to the system prompts for a language other than English
+pub fn instruction(language: &str) -> Option<String> {
+  let name = name(language);
+  (name != "English").then(|| {
+    format!(
+      "Write every summary, candidate and commit message in {name}, still in the imperative mood. \
+       Keep file paths, identifiers and code exactly as they appear in the diff."
+    )
+  })
+}
+
+/// `prompt` with the [`instruction`] for `language` appended
+pub fn system_prompt(prompt: &str, language: &str) -> String {
+  match instruction(language) {
+    Some(instruction) => format!("{prompt} {instruction}"),
+    None => prompt.to_string()
+  }
+}
+
+/// The local subject templates for `language`, English for languages without a translation
+pub(crate) fn templates(language: &str) -> &'static Templates {
+  match name(language).as_str() {
+    "Swedish" => &SWEDISH,
+    "German" => &GERMAN,
+    "French" => &FRENCH,
+    "Spanish" => &SPANISH,
+    _ => &ENGLISH
+  }
+}
+
+/// Checks a `language` setting before it is saved
+pub fn validate(language: &str) -> Result<()> {
+  let language = language.trim();
+  if language.is_empty()
+    || !language
+      .chars()
+      .all(|c| c.is_alphabetic() || matches!(c, '-' | '_' | ' '))
+  {
+    bail!("Invalid language '{language}': use a code such as sv or pt-BR, or a name such as Swedish");
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_names_and_instructions() {
+    assert_eq!(name("sv"), "Swedish");
+    assert_eq!(name("sv-SE"), "Swedish");
+    assert_eq!(name("swedish"), "Swedish");
+    assert_eq!(name("Klingon"), "Klingon");
+
+    assert_eq!(instruction("en"), None);
+    assert_eq!(instruction("en_US"), None);
+    assert!(system_prompt("Analyze the diff.", "sv")
+      .starts_with("Analyze the diff. Write every summary, candidate and commit message in Swedish"));
+    assert_eq!(system_prompt("Analyze the diff.", "English"), "Analyze the diff.");
+
+    assert!(validate("pt-BR").is_ok());
+    assert!(validate("").is_err());
+    assert!(validate("sv; rm -rf").is_err());
+  }
+}
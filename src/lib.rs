@@ -12,6 +12,24 @@ pub mod multi_step_integration;
 pub mod simple_multi_step;
 pub mod debug_output;
 pub mod generation;
+pub mod explain;
+pub mod reword;
+pub mod pr;
+pub mod changelog;
+pub mod doctor;
+pub mod picker;
+pub mod split;
+pub mod review;
+pub mod branch;
+pub mod lint;
+pub mod stash;
+pub mod tag;
+pub mod fixup;
+pub mod usage;
+pub mod serve;
+pub mod patch;
+pub mod api_key;
+pub mod language;
 
 // Re-exports
 pub use profiling::Profile;
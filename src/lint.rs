@@ -0,0 +1,314 @@
+use std::fmt;
+
+use anyhow::Result;
+use git2::{Commit, Oid, Repository};
+
+use crate::branch::change_type;
+use crate::changelog::{commit_files, parse_subject};
+use crate::commit;
+use crate::config::AppConfig;
+use crate::explain::commit_patch;
+use crate::hook::Utf8String;
+use crate::model::Model;
+use crate::multi_step_analysis::truncate_subject;
+use crate::pr::{range_commits, resolve_range};
+
+/// Body lines longer than this are reported, as git tools display them unwrapped
+pub const BODY_WRAP: usize = 72;
+
+/// Types accepted in conventional commit subjects
+pub const CONVENTIONAL_TYPES: [&str; 11] = ["feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert"];
+
+/// Verbs commit subjects start with. Only their past tense, gerund and third-person forms
+/// are reported, since plenty of valid first words end the same way ("Missing", "Nested").
+const COMMON_VERBS: &[&str] = &[
+  "add", "adjust", "allow", "apply", "avoid", "bump", "change", "check", "clean", "configure", "convert", "correct", "create", "delete",
+  "disable", "document", "drop", "enable", "ensure", "extend", "extract", "fix", "generate", "handle", "implement", "improve", "introduce",
+  "keep", "make", "merge", "migrate", "move", "optimize", "prevent", "refactor", "release", "remove", "rename", "replace", "resolve",
+  "restore", "return", "reuse", "revert", "reword", "rework", "run", "set", "show", "simplify", "skip", "split", "start", "stop",
+  "support", "tweak", "update", "upgrade", "use", "validate", "write"
+];
+
+/// A rule a commit message can violate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+  SubjectLength,
+  ImperativeMood,
+  TrailingPeriod,
+  ConventionalFormat,
+  BodyWrap
+}
+
+impl Rule {
+  /// The name shown in reports, e.g. `subject-length`
+  pub fn name(&self) -> &'static str {
+    match self {
+      Rule::SubjectLength => "subject-length",
+      Rule::ImperativeMood => "imperative-mood",
+      Rule::TrailingPeriod => "trailing-period",
+      Rule::ConventionalFormat => "conventional-format",
+      Rule::BodyWrap => "body-wrap"
+    }
+  }
+}
+
+impl fmt::Display for Rule {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+/// A single rule violation in a commit message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+  pub rule:    Rule,
+  pub message: String
+}
+
+impl fmt::Display for Violation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.rule, self.message)
+  }
+}
+
+/// The rules as configured. These are the same rules the generation prompts ask the
+/// model to follow, so generated messages pass them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+  pub max_subject_length: usize,
+  pub conventional:       bool,
+  pub body_wrap:          usize
+}
+
+impl Rules {
+  pub fn from_config(settings: &AppConfig) -> Self {
+    Rules {
+      max_subject_length: settings.max_commit_length.unwrap_or(72),
+      conventional:       settings.conventional_commits.unwrap_or(false),
+      body_wrap:          BODY_WRAP
+    }
+  }
+}
+
+/// The lint result for one commit
+#[derive(Debug, Clone)]
+pub struct CommitLint {
+  pub id:         Oid,
+  pub subject:    String,
+  pub violations: Vec<Violation>
+}
+
+/// Checks a commit message against `rules`
+pub fn lint_message(message: &str, rules: &Rules) -> Vec<Violation> {
+  let mut violations = Vec::new();
+  let mut lines = message.lines();
+  let subject = lines.next().unwrap_or_default().trim_end();
+  let violation = |rule: Rule, message: String| Violation { rule, message };
+
+  if subject.trim().is_empty() {
+    violations.push(violation(Rule::SubjectLength, "subject is empty".to_string()));
+    return violations;
+  }
+
+  let length = subject.chars().count();
+  if length > rules.max_subject_length {
+    violations.push(violation(
+      Rule::SubjectLength,
+      format!("subject is {length} characters, the limit is {}", rules.max_subject_length)
+    ));
+  }
+
+  let parsed = parse_subject(subject);
+  if let Some(word) = parsed.description.split_whitespace().next() {
+    let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+    if let Some(base) = non_imperative(word) {
+      violations.push(violation(
+        Rule::ImperativeMood,
+        format!("\"{word}\" is not imperative, use \"{}\"", capitalize_like(base, word))
+      ));
+    }
+  }
+
+  if subject.ends_with('.') {
+    violations.push(violation(Rule::TrailingPeriod, "subject ends with a period".to_string()));
+  }
+
+  if rules.conventional {
+    match parsed.kind.as_deref() {
+      None =>
+        violations.push(violation(
+          Rule::ConventionalFormat,
+          "subject does not start with `type(scope): `, e.g. `feat: add config validation`".to_string()
+        )),
+      Some(kind) if !CONVENTIONAL_TYPES.contains(&kind) =>
+        violations.push(violation(
+          Rule::ConventionalFormat,
+          format!("unknown type `{kind}`, use one of {}", CONVENTIONAL_TYPES.join(", "))
+        )),
+      Some(_) => {}
+    }
+  }
+
+  // Line 1 is the subject; long lines without spaces (URLs, paths) cannot be wrapped
+  for (i, line) in lines.enumerate() {
+    let length = line.chars().count();
+    if length > rules.body_wrap && line.trim().contains(' ') {
+      violations.push(violation(
+        Rule::BodyWrap,
+        format!("line {} is {length} characters, wrap the body at {}", i + 2, rules.body_wrap)
+      ));
+    }
+  }
+
+  violations
+}
+
+/// The imperative form of `word` when it is the past tense, gerund or third-person form
+/// of one of the [`COMMON_VERBS`]
+fn non_imperative(word: &str) -> Option<&'static str> {
+  let lower = word.to_lowercase();
+
+  let stems: Vec<String> = if let Some(stem) = lower.strip_suffix("ied") {
+    vec![format!("{stem}y")]
+  } else if let Some(stem) = lower
+    .strip_suffix("ed")
+    .or_else(|| lower.strip_suffix("ing"))
+  {
+    let mut stems = vec![stem.to_string(), format!("{stem}e")];
+    // Doubled consonants, e.g. `stopped` or `dropping`
+    let chars: Vec<char> = stem.chars().collect();
+    if chars.len() > 2 && chars[chars.len() - 1] == chars[chars.len() - 2] {
+      stems.push(chars[..chars.len() - 1].iter().collect());
+    }
+    stems
+  } else {
+    let stem = lower.strip_suffix('s')?;
+    return COMMON_VERBS
+      .iter()
+      .copied()
+      .find(|verb| *verb == stem || Some(*verb) == stem.strip_suffix('e'));
+  };
+
+  stems
+    .iter()
+    .find_map(|stem| COMMON_VERBS.iter().copied().find(|verb| verb == stem))
+}
+
+fn capitalize_like(base: &str, word: &str) -> String {
+  if word.chars().next().is_some_and(char::is_uppercase) {
+    let mut chars = base.chars();
+    chars
+      .next()
+      .map(|first| first.to_uppercase().chain(chars).collect())
+      .unwrap_or_default()
+  } else {
+    base.to_string()
+  }
+}
+
+/// Lints every non-merge commit in `range` (`base..head`, or `base` meaning `base..HEAD`)
+pub fn lint_range(repo: &Repository, range: &str, rules: &Rules) -> Result<Vec<CommitLint>> {
+  let (base, head) = resolve_range(repo, range)?;
+  Ok(
+    range_commits(repo, &base, &head)?
+      .iter()
+      .map(|commit| {
+        let message = commit.message_bytes().to_utf8();
+        CommitLint {
+          id:         commit.id(),
+          subject:    message.lines().next().unwrap_or_default().to_string(),
+          violations: lint_message(&message, rules)
+        }
+      })
+      .collect()
+  )
+}
+
+/// Generates a subject for `commit` from its diff and adjusts it to `rules`: no trailing
+/// period, a conventional prefix when required, and within the length limit.
+pub async fn suggest(repo: &Repository, commit: &Commit<'_>, rules: &Rules, model: Model) -> Result<String> {
+  let remaining_tokens = commit::remaining_tokens(&model)?;
+  let patch = commit_patch(repo, commit, remaining_tokens, model.clone())?;
+  let generated = commit::generate(patch, remaining_tokens, model, None)
+    .await?
+    .response;
+  let subject = generated
+    .lines()
+    .next()
+    .unwrap_or_default()
+    .trim()
+    .trim_end_matches('.')
+    .to_string();
+
+  let subject = if rules.conventional && parse_subject(&subject).kind.is_none() {
+    let kind = change_type(&subject, &commit_files(repo, commit)?);
+    let mut chars = subject.chars();
+    let description: String = chars
+      .next()
+      .map(|first| first.to_lowercase().chain(chars).collect())
+      .unwrap_or_default();
+    format!("{kind}: {description}")
+  } else {
+    subject
+  };
+
+  Ok(truncate_subject(subject, rules.max_subject_length))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rules(conventional: bool) -> Rules {
+    Rules {
+      max_subject_length: 50,
+      conventional,
+      body_wrap: BODY_WRAP
+    }
+  }
+
+  fn rule_names(message: &str, conventional: bool) -> Vec<&'static str> {
+    lint_message(message, &rules(conventional))
+      .iter()
+      .map(|violation| violation.rule.name())
+      .collect()
+  }
+
+  #[test]
+  fn test_lint_message_accepts_compliant_messages() {
+    assert!(rule_names("Add config validation", false).is_empty());
+    assert!(rule_names("feat(config): add validation\n\nRejects zero timeouts.", true).is_empty());
+    assert!(rule_names("[ABC-1] Fix crash in the hook", false).is_empty());
+  }
+
+  #[test]
+  fn test_lint_message_reports_each_rule() {
+    let long_body = format!("Fix crash\n\n{}", "word ".repeat(20));
+    assert_eq!(rule_names(&long_body, false), vec!["body-wrap"]);
+    assert_eq!(rule_names("Added config validation.", false), vec!["imperative-mood", "trailing-period"]);
+    assert_eq!(rule_names(&"Add ".repeat(20), false), vec!["subject-length"]);
+    assert_eq!(rule_names("Add config validation", true), vec!["conventional-format"]);
+    assert_eq!(rule_names("feature: add config validation", true), vec!["conventional-format"]);
+  }
+
+  #[test]
+  fn test_imperative_suggestions() {
+    let message = |subject: &str| lint_message(subject, &rules(false))[0].message.clone();
+    assert_eq!(message("Added validation"), "\"Added\" is not imperative, use \"Add\"");
+    assert_eq!(message("Updates the hook"), "\"Updates\" is not imperative, use \"Update\"");
+    assert_eq!(message("fixing the hook"), "\"fixing\" is not imperative, use \"fix\"");
+    assert_eq!(message("Stopped the spinner"), "\"Stopped\" is not imperative, use \"Stop\"");
+    assert!(rule_names("Docs for the hook", false).is_empty());
+    assert!(rule_names("Bring back the spinner", false).is_empty());
+  }
+
+  #[test]
+  fn test_words_that_only_look_like_verb_forms_pass() {
+    for subject in [
+      "Missing config no longer crashes the hook", "Logging for the hook", "Nested profiles in config.toml", "Unified diff support",
+      "Embedding model settings", "Speed up the diff", "Things to know about the hook"
+    ] {
+      assert!(rule_names(subject, false).is_empty(), "{subject}");
+    }
+  }
+}
@@ -1,9 +1,16 @@
+use std::io::Write;
+
 use structopt::StructOpt;
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use dotenv::dotenv;
-use ai::config::AppConfig;
+use git2::Repository;
+use ai::config::{AppConfig, ConfigKey, ConfigScope};
 use ai::filesystem::Filesystem;
-use ai::{model, openai};
+use ai::hook::{PatchDiff, PatchRepository};
+use ai::{
+  branch, changelog, commit, config, doctor, explain, fixup, language, lint, model, openai, patch, pr, review, reword, serve, split, stash, tag, usage
+};
+use ai::multi_step_analysis::Severity;
 
 #[derive(StructOpt)]
 #[structopt(name = "git-ai", about = "A git extension that uses OpenAI to generate commit messages")]
@@ -11,7 +18,160 @@ enum Cli {
   #[structopt(about = "Installs the git-ai hook")]
   Hook(HookSubcommand),
   #[structopt(about = "Sets or gets configuration values")]
-  Config(ConfigSubcommand)
+  Config(ConfigSubcommand),
+  #[structopt(about = "Lists configuration profiles or switches the active one")]
+  Profile(ProfileSubcommand),
+  #[structopt(about = "Generates a commit message for the staged changes without committing")]
+  Generate(GenerateArgs),
+  #[structopt(about = "Explains what an existing commit changed, file by file")]
+  Explain(ExplainArgs),
+  #[structopt(about = "Regenerates the messages of the commits in a range and rewrites them")]
+  Reword(RewordArgs),
+  #[structopt(about = "Generates a pull request title and description for a branch range")]
+  Pr(PrArgs),
+  #[structopt(about = "Generates a Keep a Changelog section from the commits in a range")]
+  Changelog(ChangelogArgs),
+  #[structopt(about = "Checks the hook, configuration, API key, endpoint and model")]
+  Doctor,
+  #[structopt(about = "Lists the models available at the configured endpoint")]
+  Models(ModelsArgs),
+  #[structopt(about = "Splits the staged changes into a series of smaller commits")]
+  Split(SplitArgs),
+  #[structopt(about = "Reviews the staged changes for secrets, debug leftovers and obvious bugs")]
+  Review(ReviewArgs),
+  #[structopt(about = "Suggests branch names for the uncommitted changes and optionally switches to one")]
+  Branch(BranchArgs),
+  #[structopt(about = "Checks the commit messages in a range against the message rules")]
+  Lint(LintArgs),
+  #[structopt(about = "Stashes the local changes under a generated message, or lists stashes with summaries")]
+  Stash(StashArgs),
+  #[structopt(about = "Suggests the next version and creates an annotated tag summarizing the release")]
+  Tag(TagArgs),
+  #[structopt(about = "Turns the staged hunks into fixup! commits for the unpushed commits they amend")]
+  Fixup(FixupArgs),
+  #[structopt(about = "Reports token usage and estimated cost from the local usage ledger")]
+  Usage(UsageArgs),
+  #[structopt(about = "Serves JSON-RPC requests over stdin and stdout for editor integrations")]
+  Serve
+}
+
+#[derive(StructOpt)]
+struct GenerateArgs {
+  #[structopt(long, help = "Prints the message, reasoning, per-file summaries and strategy as JSON")]
+  json: bool,
+
+  #[structopt(long, help = "The model to use for this run only")]
+  model: Option<String>,
+
+  #[structopt(long = "max-length", help = "The maximum length of the commit message for this run only")]
+  max_length: Option<usize>,
+
+  #[structopt(long, help = "Reads a unified diff or a git format-patch series from stdin instead of the staged changes")]
+  stdin: bool,
+
+  #[structopt(
+    long,
+    help = "Reads unified diffs or git format-patch files instead of the staged changes, one message per patch",
+    conflicts_with = "stdin",
+    parse(from_os_str)
+  )]
+  patch: Vec<std::path::PathBuf>
+}
+
+#[derive(StructOpt)]
+struct ExplainArgs {
+  #[structopt(help = "The commit to explain, e.g. HEAD~2 or a commit hash", name = "REV", default_value = "HEAD")]
+  rev: String,
+
+  #[structopt(long, help = "Prints the explanation as JSON")]
+  json: bool,
+
+  #[structopt(long, help = "The model to use for this run only")]
+  model: Option<String>
+}
+
+#[derive(StructOpt)]
+struct RewordArgs {
+  #[structopt(help = "The commits to reword, e.g. main..HEAD or HEAD~5", name = "RANGE")]
+  range: String,
+
+  #[structopt(short, long, help = "Rewrites without asking for confirmation")]
+  yes: bool,
+
+  #[structopt(long, help = "Also rewrites commits that are already pushed")]
+  force: bool,
+
+  #[structopt(long, help = "The model to use for this run only")]
+  model: Option<String>
+}
+
+#[derive(StructOpt)]
+struct PrArgs {
+  #[structopt(help = "The branch range, e.g. main..feature or main (meaning main..HEAD)", name = "RANGE")]
+  range: String,
+
+  #[structopt(short, long, help = "Writes the title and description to this file instead of stdout")]
+  output: Option<std::path::PathBuf>,
+
+  #[structopt(long, help = "The model to use for this run only")]
+  model: Option<String>
+}
+
+#[derive(StructOpt)]
+struct ChangelogArgs {
+  #[structopt(help = "The commits to include, e.g. v1.2.0..HEAD or v1.2.0 (meaning v1.2.0..HEAD)", name = "RANGE")]
+  range: String,
+
+  #[structopt(long, help = "The version the section is for", default_value = "Unreleased", name = "VERSION")]
+  release: String,
+
+  #[structopt(long, help = "Prepends the section to the changelog file instead of printing it")]
+  prepend: bool,
+
+  #[structopt(long, help = "The changelog file used with --prepend", default_value = "CHANGELOG.md", parse(from_os_str))]
+  file: std::path::PathBuf
+}
+
+#[derive(StructOpt)]
+struct ModelsArgs {
+  #[structopt(long, help = "Probes every model with a forced commit tool call to see which support it")]
+  probe: bool
+}
+
+#[derive(StructOpt)]
+struct SplitArgs {
+  #[structopt(long = "dry-run", help = "Only shows the proposed commits")]
+  dry_run: bool,
+
+  #[structopt(short, long, help = "Commits without asking for confirmation")]
+  yes: bool,
+
+  #[structopt(long, help = "The model to use for this run only")]
+  model: Option<String>
+}
+
+#[derive(StructOpt)]
+struct ReviewArgs {
+  #[structopt(long, help = "Stays quiet without findings and fails on high-severity ones, for use in a pre-commit hook")]
+  hook: bool,
+
+  #[structopt(long, help = "The model to use for this run only")]
+  model: Option<String>
+}
+
+#[derive(StructOpt)]
+struct BranchArgs {
+  #[structopt(short, long, help = "Creates the suggestion with this number (the first one if omitted) and switches to it", name = "N")]
+  create: Option<Option<usize>>,
+
+  #[structopt(long, help = "The ticket for patterns that use <ticket>, e.g. ABC-123")]
+  ticket: Option<String>,
+
+  #[structopt(long, help = "The branch pattern for this run only, e.g. <ticket>-<slug>")]
+  pattern: Option<String>,
+
+  #[structopt(long, help = "The model to use for this run only")]
+  model: Option<String>
 }
 
 #[derive(StructOpt)]
@@ -27,12 +187,114 @@ enum HookSubcommand {
 #[derive(StructOpt)]
 enum ConfigSubcommand {
   #[structopt(about = "Sets a configuration value")]
-  Set(SetSubcommand),
+  Set {
+    #[structopt(long, help = "Writes the value to the current repository's .git/config instead of the global config")]
+    local: bool,
+
+    #[structopt(long, help = "Writes the value to the named profile, creating it if needed", name = "NAME", conflicts_with = "local")]
+    profile: Option<String>,
+
+    #[structopt(subcommand)]
+    set: SetSubcommand
+  },
+
+  #[structopt(about = "Shows the effective value of a configuration key and where it came from")]
+  Get {
+    #[structopt(help = "The configuration key, e.g. model or max-tokens", name = "KEY")]
+    key: ConfigKey
+  },
+
+  #[structopt(about = "Lists all effective configuration values and where they came from")]
+  List,
+
+  #[structopt(about = "Removes a configuration value so its default applies again")]
+  Unset {
+    #[structopt(long, help = "Removes the value from the current repository's .git/config instead of the global config")]
+    local: bool,
+
+    #[structopt(long, help = "Removes the value from the named profile", name = "NAME", conflicts_with = "local")]
+    profile: Option<String>,
+
+    #[structopt(help = "The configuration key, e.g. model or max-tokens", name = "KEY")]
+    key: ConfigKey
+  },
 
   #[structopt(about = "Resets the internal configuration to the default values")]
   Reset
 }
 
+#[derive(StructOpt)]
+enum ProfileSubcommand {
+  #[structopt(about = "Applies the named profile by default; GIT_AI_PROFILE overrides it per invocation")]
+  Use {
+    #[structopt(help = "The profile name", name = "NAME")]
+    name: String
+  },
+
+  #[structopt(about = "Lists the profiles, marking the active one")]
+  List
+}
+
+#[derive(StructOpt)]
+struct LintArgs {
+  #[structopt(help = "The commits to check, e.g. main..feature or main (meaning main..HEAD)", name = "RANGE")]
+  range: String,
+
+  #[structopt(long, help = "Generates a compliant replacement subject for each failing commit")]
+  suggest: bool,
+
+  #[structopt(long, help = "The model to use for --suggest")]
+  model: Option<String>
+}
+
+#[derive(StructOpt)]
+struct StashArgs {
+  #[structopt(subcommand)]
+  command: Option<StashSubcommand>,
+
+  #[structopt(long, help = "The model to use for this run only")]
+  model: Option<String>
+}
+
+#[derive(StructOpt)]
+enum StashSubcommand {
+  #[structopt(about = "Lists the stashes, with generated summaries for those without a message")]
+  List {
+    #[structopt(long, help = "The model to use for this run only")]
+    model: Option<String>
+  }
+}
+
+#[derive(StructOpt)]
+struct TagArgs {
+  #[structopt(help = "The tag name, instead of the suggested next version", name = "NAME")]
+  name: Option<String>,
+
+  #[structopt(short, long, help = "Creates the tag without asking for confirmation")]
+  yes: bool
+}
+
+#[derive(StructOpt)]
+struct FixupArgs {
+  #[structopt(long = "dry-run", help = "Only shows which commits the hunks would amend")]
+  dry_run: bool,
+
+  #[structopt(short, long, help = "Commits without asking for confirmation")]
+  yes: bool,
+
+  #[structopt(long, help = "The model to use for hunks that need a regular commit")]
+  model: Option<String>
+}
+
+#[derive(StructOpt)]
+struct UsageArgs {
+  #[structopt(long, help = "Groups the report by day, repo or model", default_value = "day", name = "GROUP")]
+  by: usage::GroupBy,
+
+  #[structopt(long, help = "Only includes the last N days", name = "N")]
+  days: Option<u64>
+}
+
 #[derive(StructOpt)]
 enum SetSubcommand {
   #[structopt(about = "Sets the model to use")]
@@ -56,10 +318,58 @@ enum SetSubcommand {
     value: String
   },
 
+  #[structopt(about = "Reads the OpenAI API key from the first line a command prints, e.g. `pass show openai`")]
+  OpenaiApiKeyCommand {
+    #[structopt(help = "The command, run with sh -c when a request is made", name = "VALUE")]
+    value: String
+  },
+
+  #[structopt(about = "Reads the OpenAI API key from a file only you can read (chmod 600)")]
+  OpenaiApiKeyFile {
+    #[structopt(help = "The path of the file, ~/ is expanded", name = "VALUE")]
+    value: String
+  },
+
+  #[structopt(about = "Reads the OpenAI API key from another environment variable")]
+  OpenaiApiKeyEnv {
+    #[structopt(help = "The name of the variable, e.g. WORK_OPENAI_KEY", name = "VALUE")]
+    value: String
+  },
+
+  #[structopt(about = "Refuses to store the OpenAI API key itself in the configuration")]
+  ForbidPlaintextApiKey {
+    #[structopt(help = "true or false", name = "VALUE", parse(try_from_str))]
+    value: bool
+  },
+
   #[structopt(about = "Sets a custom OpenAI-compatible base URL (e.g. a local ollama endpoint)")]
   OpenaiBaseUrl {
     #[structopt(help = "The base URL, e.g. http://localhost:11434/v1", name = "VALUE")]
     value: String
+  },
+
+  #[structopt(about = "Sets the request timeout in seconds")]
+  Timeout {
+    #[structopt(help = "The timeout in seconds", name = "timeout")]
+    timeout: usize
+  },
+
+  #[structopt(about = "Sets the pattern for suggested branch names")]
+  BranchPattern {
+    #[structopt(help = "The pattern, using <type>, <slug> and <ticket>, e.g. <ticket>-<slug>", name = "VALUE")]
+    value: String
+  },
+
+  #[structopt(about = "Requires conventional commit subjects, e.g. feat(config): add validation")]
+  ConventionalCommits {
+    #[structopt(help = "true or false", name = "VALUE", parse(try_from_str))]
+    value: bool
+  },
+
+  #[structopt(about = "Sets the language commit messages are written in")]
+  Language {
+    #[structopt(help = "A code such as sv or pt-BR, or a name such as Swedish", name = "VALUE")]
+    value: String
   }
 }
 
@@ -107,12 +417,7 @@ fn run_reinstall() -> Result<()> {
 
 // Config management functions
 fn run_config_reset() -> Result<()> {
-  let config_dir = dirs::config_dir()
-    .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?
-    .join("git-ai");
-
-  if config_dir.exists() {
-    std::fs::remove_dir_all(&config_dir)?;
+  if config::reset()? {
     println!("🗑️  Configuration reset successfully");
   } else {
     println!("⚠️  No configuration found to reset");
@@ -121,13 +426,44 @@ fn run_config_reset() -> Result<()> {
   Ok(())
 }
 
-async fn run_config_model(value: String) -> Result<()> {
+fn run_config_get(key: ConfigKey) -> Result<()> {
+  let entry = AppConfig::entry(key)?;
+  println!("{} ({})", entry.display_value(), entry.source);
+  Ok(())
+}
+
+fn config_scope(local: bool, profile: Option<String>) -> ConfigScope {
+  match (local, profile) {
+    (true, _) => ConfigScope::Local,
+    (false, Some(name)) => ConfigScope::Profile(name),
+    (false, None) => ConfigScope::Global
+  }
+}
+
+fn run_config_unset(key: ConfigKey, scope: &ConfigScope) -> Result<()> {
+  AppConfig::unset(scope, key)
+}
+
+fn run_config_list() -> Result<()> {
+  for entry in AppConfig::entries()? {
+    println!("{:<24} {:<32} ({})", entry.key.name(), entry.display_value(), entry.source);
+  }
+  Ok(())
+}
+
+async fn run_config_model(value: String, scope: &ConfigScope) -> Result<()> {
   let value = value.trim().to_string();
   if value.is_empty() {
     anyhow::bail!("Model name cannot be empty");
   }
 
+  // A profile's model is verified against that profile's endpoint
   let mut app = AppConfig::new()?;
+  if let ConfigScope::Profile(name) = scope {
+    if let Some(profile) = app.profiles.get(name).cloned() {
+      app.use_endpoint_of(&profile);
+    }
+  }
 
   // Verify the model exists at the configured endpoint before saving. Known and
   // deprecated aliases skip the round-trip; unreachable/unauthorized endpoints
@@ -136,39 +472,679 @@ async fn run_config_model(value: String) -> Result<()> {
   let known_or_deprecated = model::is_known_or_deprecated(&value);
   openai::verify_model_exists(&app, &value, known_or_deprecated).await?;
 
-  app.update_model(value.clone())?;
+  AppConfig::set(scope, ConfigKey::Model, &value)?;
   println!("✅ Model set to: {value}");
   Ok(())
 }
 
-fn run_config_max_tokens(max_tokens: usize) -> Result<()> {
-  let mut app = AppConfig::new()?;
-  app.update_max_tokens(max_tokens)?;
+fn run_config_max_tokens(max_tokens: usize, scope: &ConfigScope) -> Result<()> {
+  AppConfig::set(scope, ConfigKey::MaxTokens, &max_tokens.to_string())?;
   println!("✅ Max tokens set to: {max_tokens}");
   Ok(())
 }
 
-fn run_config_max_commit_length(max_commit_length: usize) -> Result<()> {
-  let mut app = AppConfig::new()?;
-  app.update_max_commit_length(max_commit_length)?;
+fn run_config_max_commit_length(max_commit_length: usize, scope: &ConfigScope) -> Result<()> {
+  AppConfig::set(scope, ConfigKey::MaxCommitLength, &max_commit_length.to_string())?;
   println!("✅ Max commit length set to: {max_commit_length}");
   Ok(())
 }
 
-fn run_config_openai_api_key(value: String) -> Result<()> {
-  let mut app = AppConfig::new()?;
-  app.update_openai_api_key(value)?;
+fn run_config_openai_api_key(value: String, scope: &ConfigScope) -> Result<()> {
+  AppConfig::set(scope, ConfigKey::OpenaiApiKey, &value)?;
   println!("✅ OpenAI API key updated");
   Ok(())
 }
 
-fn run_config_openai_base_url(value: String) -> Result<()> {
-  let mut app = AppConfig::new()?;
-  app.update_openai_base_url(value.clone())?;
+fn run_config_api_key_source(key: ConfigKey, value: String, scope: &ConfigScope) -> Result<()> {
+  AppConfig::set(scope, key, &value)?;
+  let shown = match key {
+    ConfigKey::OpenaiApiKeyCommand => config::mask_command(&value),
+    _ => value
+  };
+  println!("✅ OpenAI API key will be read with {key}: {shown}");
+  Ok(())
+}
+
+fn run_config_forbid_plaintext_api_key(value: bool, scope: &ConfigScope) -> Result<()> {
+  AppConfig::set(scope, ConfigKey::ForbidPlaintextApiKey, &value.to_string())?;
+  println!("✅ Forbid plaintext API key set to: {value}");
+  if value && AppConfig::from_file()?.openai_api_key.is_some() {
+    println!("⚠️  config.toml still stores an API key; remove it with `git ai config unset openai-api-key`");
+  }
+  Ok(())
+}
+
+fn run_config_openai_base_url(value: String, scope: &ConfigScope) -> Result<()> {
+  AppConfig::set(scope, ConfigKey::OpenaiBaseUrl, &value)?;
   println!("✅ OpenAI base URL set to: {value}");
   Ok(())
 }
 
+fn run_config_timeout(timeout: usize, scope: &ConfigScope) -> Result<()> {
+  AppConfig::set(scope, ConfigKey::Timeout, &timeout.to_string())?;
+  println!("✅ Timeout set to: {timeout}s");
+  Ok(())
+}
+
+fn run_config_branch_pattern(value: String, scope: &ConfigScope) -> Result<()> {
+  AppConfig::set(scope, ConfigKey::BranchPattern, &value)?;
+  println!("✅ Branch pattern set to: {value}");
+  Ok(())
+}
+
+fn run_config_conventional_commits(value: bool, scope: &ConfigScope) -> Result<()> {
+  AppConfig::set(scope, ConfigKey::ConventionalCommits, &value.to_string())?;
+  println!("✅ Conventional commits set to: {value}");
+  Ok(())
+}
+
+fn run_config_language(value: String, scope: &ConfigScope) -> Result<()> {
+  AppConfig::set(scope, ConfigKey::Language, &value)?;
+  println!("✅ Language set to: {}", language::name(&value));
+  Ok(())
+}
+
+fn run_profile_use(name: &str) -> Result<()> {
+  AppConfig::use_profile(name)?;
+  println!("✅ Using profile: {name}");
+  if let Some(active) = std::env::var(config::PROFILE_ENV)
+    .ok()
+    .filter(|active| !active.trim().is_empty() && active != name)
+  {
+    println!("⚠️  {} is set to '{active}' and overrides this in the current shell", config::PROFILE_ENV);
+  }
+  Ok(())
+}
+
+fn run_profile_list() -> Result<()> {
+  let stored = AppConfig::from_file()?;
+  if stored.profiles.is_empty() {
+    println!("No profiles yet. Create one with `git ai config set --profile <name> <key> <value>`");
+    return Ok(());
+  }
+
+  let active = stored.active_profile();
+  let width = stored.profiles.keys().map(String::len).max().unwrap_or(0);
+  for (name, profile) in &stored.profiles {
+    let marker = if active.as_deref() == Some(name.as_str()) {
+      "*"
+    } else {
+      " "
+    };
+    let model = profile.model.as_deref().unwrap_or("-");
+    let base_url = profile.openai_base_url.as_deref().unwrap_or("-");
+    println!("{marker} {name:<width$}  {model:<16} {base_url}");
+  }
+  Ok(())
+}
+
+/// The model given on the command line, else the configured one, else the default
+fn resolve_model(arg: Option<String>) -> model::Model {
+  arg
+    .or_else(|| config::APP_CONFIG.model.clone())
+    .unwrap_or(config::DEFAULT_MODEL.to_string())
+    .into()
+}
+
+async fn run_generate(args: GenerateArgs) -> Result<()> {
+  let model = resolve_model(args.model.clone());
+  let remaining_tokens = commit::remaining_tokens(&model)?;
+
+  // Only build per-run settings when a one-off override was requested; otherwise use the
+  // same global configuration path as the hook (which also honors `OPENAI_API_KEY`).
+  let settings = match args.max_length {
+    Some(max_length) => {
+      let mut settings = openai::with_env_api_key(AppConfig::new()?);
+      settings.max_commit_length = Some(max_length);
+      Some(settings)
+    }
+    None => None
+  };
+
+  if args.stdin || !args.patch.is_empty() {
+    return generate_for_patches(&args, remaining_tokens, model, settings.as_ref()).await;
+  }
+
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+  if repo.to_commit_diff(tree.clone())?.is_empty()? {
+    bail!("No staged changes to generate a commit message for");
+  }
+
+  let patch = repo
+    .to_patch(tree, remaining_tokens, model.clone())
+    .context("Failed to get patch")?;
+
+  let response = commit::generate_response(patch, remaining_tokens, model, settings.as_ref()).await?;
+
+  if args.json {
+    println!("{}", serde_json::to_string_pretty(&response)?);
+  } else {
+    println!("{}", response.message.trim());
+  }
+
+  Ok(())
+}
+
+/// Generates one message per patch read from `--stdin` or `--patch`. Several patches are
+/// printed under `==> name <==` headers. `--json` always prints an array with one item per
+/// patch, each carrying its `patch` name, even when there is a single patch.
+async fn generate_for_patches(
+  args: &GenerateArgs, remaining_tokens: usize, model: model::Model, settings: Option<&AppConfig>
+) -> Result<()> {
+  let mut patches = Vec::new();
+  if args.stdin {
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut content).context("Failed to read the diff from stdin")?;
+    patches.extend(patch::parse("stdin", &content));
+  }
+  for path in &args.patch {
+    patches.extend(patch::read(path)?);
+  }
+  if patches.is_empty() {
+    bail!("No file changes found in the given diff");
+  }
+
+  let several = patches.len() > 1;
+  let mut results = Vec::with_capacity(patches.len());
+  for (i, patch) in patches.into_iter().enumerate() {
+    let diff = model.truncate(&patch.diff, remaining_tokens)?;
+    let response = commit::generate_response(diff, remaining_tokens, model.clone(), settings)
+      .await
+      .with_context(|| format!("Failed to generate a message for {}", patch.name))?;
+
+    if args.json {
+      let mut value = serde_json::to_value(&response)?;
+      value["patch"] = patch.name.into();
+      results.push(value);
+    } else if several {
+      if i > 0 {
+        println!();
+      }
+      println!("==> {} <==\n{}", patch.name, response.message.trim());
+    } else {
+      println!("{}", response.message.trim());
+    }
+  }
+
+  if args.json {
+    println!("{}", serde_json::to_string_pretty(&results)?);
+  }
+
+  Ok(())
+}
+
+async fn run_explain(args: ExplainArgs) -> Result<()> {
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let model = resolve_model(args.model);
+
+  let explanation = explain::explain(&repo, &args.rev, model, None).await?;
+
+  if args.json {
+    println!("{}", serde_json::to_string_pretty(&explanation)?);
+  } else {
+    print!("{explanation}");
+  }
+
+  Ok(())
+}
+
+/// Truncates `text` to `width` characters, marking the cut with an ellipsis
+fn truncate_column(text: &str, width: usize) -> String {
+  if text.chars().count() <= width {
+    text.to_string()
+  } else {
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+  }
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+  print!("{prompt} [y/N] ");
+  std::io::stdout().flush()?;
+  let mut answer = String::new();
+  std::io::stdin().read_line(&mut answer)?;
+  Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+async fn run_reword(args: RewordArgs) -> Result<()> {
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let model = resolve_model(args.model);
+
+  let commits = reword::commits_in_range(&repo, &args.range)?;
+  let pushed = reword::pushed_commits(&repo, &commits)?;
+  if !pushed.is_empty() && !args.force {
+    bail!(
+      "{} of {} commits in '{}' are already pushed (e.g. {:.7}); use --force to rewrite them anyway",
+      pushed.len(),
+      commits.len(),
+      args.range,
+      pushed[0]
+    );
+  }
+
+  println!("Generating messages for {} commits...", commits.len());
+  let rewordings = reword::generate_rewordings(&repo, &commits, model).await?;
+
+  const WIDTH: usize = 50;
+  println!();
+  println!("{:<7}  {:<WIDTH$}  After", "Commit", "Before");
+  for rewording in &rewordings {
+    println!(
+      "{:.7}  {:<WIDTH$}  {}",
+      rewording.id,
+      truncate_column(rewording.old_subject(), WIDTH),
+      rewording.new_subject()
+    );
+  }
+  println!();
+
+  if rewordings
+    .iter()
+    .all(|rewording| rewording.old_message.trim() == rewording.new_message.trim())
+  {
+    println!("Nothing to reword");
+    return Ok(());
+  }
+
+  if !args.yes && !confirm(&format!("Rewrite {} commits?", rewordings.len()))? {
+    println!("Aborted, nothing was changed");
+    return Ok(());
+  }
+
+  let new_tip = reword::rewrite(&repo, &rewordings)?;
+  println!(
+    "✅ Reworded {} commits, HEAD is now {:.7} (previous tip saved under {}*)",
+    rewordings.len(),
+    new_tip,
+    reword::BACKUP_REF_PREFIX
+  );
+
+  Ok(())
+}
+
+async fn run_pr(args: PrArgs) -> Result<()> {
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let model = resolve_model(args.model);
+
+  let pull_request = pr::generate(&repo, &args.range, model, None).await?;
+
+  match args.output {
+    Some(path) => {
+      std::fs::write(&path, pull_request.to_string()).with_context(|| format!("Failed to write {}", path.display()))?;
+      println!("✅ Pull request description written to {}", path.display());
+    }
+    None => print!("{pull_request}")
+  }
+
+  Ok(())
+}
+
+fn run_changelog(args: ChangelogArgs) -> Result<()> {
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let section = changelog::generate(&repo, &args.range, &args.release)?;
+
+  if args.prepend {
+    // A relative changelog path is relative to the repository root, not the current directory
+    let path = match repo.workdir() {
+      Some(workdir) if args.file.is_relative() => workdir.join(&args.file),
+      _ => args.file
+    };
+    changelog::prepend(&path, &section)?;
+    println!("✅ Changelog section added to {}", path.display());
+  } else {
+    print!("{section}");
+  }
+
+  Ok(())
+}
+
+async fn run_doctor() -> Result<()> {
+  let checks = doctor::run().await;
+  for check in &checks {
+    println!("{check}");
+  }
+
+  let failed = checks
+    .iter()
+    .filter(|check| check.status == doctor::Status::Fail)
+    .count();
+  if failed > 0 {
+    bail!("{failed} check(s) failed");
+  }
+
+  println!("\nEverything looks good");
+  Ok(())
+}
+
+async fn run_models(args: ModelsArgs) -> Result<()> {
+  let settings = openai::with_env_api_key(AppConfig::new()?);
+  let configured: model::Model = settings
+    .model
+    .clone()
+    .unwrap_or(config::DEFAULT_MODEL.to_string())
+    .into();
+  let configured = configured.to_string();
+
+  let mut ids = openai::list_models(&settings)
+    .await
+    .context("Failed to list the models at the configured endpoint (see `git ai doctor`)")?;
+  ids.sort();
+
+  println!(
+    "  {:<40} {:>10}{}",
+    "Model",
+    "Context",
+    if args.probe {
+      "  Tool calls"
+    } else {
+      ""
+    }
+  );
+  for id in &ids {
+    let marker = if *id == configured {
+      "*"
+    } else {
+      " "
+    };
+    let context = model::Model::from(id.as_str())
+      .known_context_size()
+      .map_or("-".to_string(), |size| size.to_string());
+
+    let tool_calls = if args.probe {
+      match openai::supports_forced_tool_calls(&settings, id).await {
+        Ok(true) => "  yes".to_string(),
+        Ok(false) => "  no (tool call ignored)".to_string(),
+        Err(e) => format!("  no ({})", e.to_string().lines().next().unwrap_or_default())
+      }
+    } else {
+      String::new()
+    };
+
+    println!("{marker} {id:<40} {context:>10}{tool_calls}");
+  }
+
+  if !ids.contains(&configured) {
+    println!("\n⚠️  The configured model {configured} is not offered by this endpoint");
+  }
+
+  Ok(())
+}
+
+async fn run_split(args: SplitArgs) -> Result<()> {
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let model = resolve_model(args.model);
+
+  if !args.dry_run {
+    // Fail before generating messages rather than after
+    repo
+      .signature()
+      .context("Failed to determine the committer; set user.name and user.email")?;
+  }
+
+  let planned = split::plan(&repo, model).await?;
+  if planned.len() < 2 {
+    println!("The staged changes form a single logical change; commit them as they are");
+    return Ok(());
+  }
+
+  println!("Proposed commits:");
+  for (i, commit) in planned.iter().enumerate() {
+    println!("\n{}. {}", i + 1, commit.message.lines().next().unwrap_or_default());
+    for (path, added, removed) in commit.group.files() {
+      println!("     {} (+{added} -{removed})", path.display());
+    }
+  }
+  println!();
+
+  if args.dry_run {
+    println!("Dry run, nothing was committed");
+    return Ok(());
+  }
+
+  if !args.yes && !confirm(&format!("Create {} commits?", planned.len()))? {
+    println!("Aborted, nothing was committed");
+    return Ok(());
+  }
+
+  let commits = split::apply(&repo, &planned)?;
+  println!(
+    "✅ Created {} commits, HEAD is now {:.7}",
+    commits.len(),
+    commits.last().copied().unwrap_or(git2::Oid::ZERO_SHA1)
+  );
+
+  Ok(())
+}
+
+async fn run_review(args: ReviewArgs) -> Result<()> {
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let settings = openai::with_env_api_key(AppConfig::new()?);
+  let model = resolve_model(args.model.or_else(|| settings.model.clone()));
+
+  let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+  if args.hook && repo.to_commit_diff(head)?.is_empty()? {
+    // Nothing to review, e.g. `git commit --allow-empty`
+    return Ok(());
+  }
+
+  let findings = review::review(&repo, model, &settings).await?;
+  if findings.is_empty() {
+    if !args.hook {
+      println!("✅ No findings");
+    }
+    return Ok(());
+  }
+
+  for finding in &findings {
+    println!("{finding}");
+  }
+  let count = |severity: Severity| findings.iter().filter(|f| f.severity == severity).count();
+  println!(
+    "\n{} findings ({} high, {} medium, {} low)",
+    findings.len(),
+    count(Severity::High),
+    count(Severity::Medium),
+    count(Severity::Low)
+  );
+
+  if args.hook && review::blocks_commit(&findings) {
+    bail!("Commit blocked by high-severity findings; fix them or commit with --no-verify");
+  }
+
+  Ok(())
+}
+
+async fn run_branch(args: BranchArgs) -> Result<()> {
+  const SUGGESTIONS: usize = 3;
+
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let model = resolve_model(args.model);
+  let pattern = args
+    .pattern
+    .or_else(|| config::APP_CONFIG.branch_pattern.clone())
+    .unwrap_or(config::DEFAULT_BRANCH_PATTERN.to_string());
+
+  let names = branch::suggest(&repo, &pattern, args.ticket.as_deref(), SUGGESTIONS, model, None).await?;
+
+  let Some(choice) = args.create else {
+    for (i, name) in names.iter().enumerate() {
+      println!("{}. {name}", i + 1);
+    }
+    println!("\nRun `git ai branch --create <N>` to create one and switch to it");
+    return Ok(());
+  };
+
+  let index = choice.unwrap_or(1);
+  let name = names
+    .get(index.wrapping_sub(1))
+    .with_context(|| format!("There is no suggestion {index}, pick one between 1 and {}", names.len()))?;
+  branch::create_and_switch(&repo, name)?;
+  println!("✅ Switched to new branch {name}; your changes came along");
+
+  Ok(())
+}
+
+async fn run_lint(args: LintArgs) -> Result<()> {
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let model = resolve_model(args.model);
+  let rules = lint::Rules::from_config(&config::APP_CONFIG);
+
+  let results = lint::lint_range(&repo, &args.range, &rules)?;
+  let failing: Vec<&lint::CommitLint> = results
+    .iter()
+    .filter(|result| !result.violations.is_empty())
+    .collect();
+
+  for result in &failing {
+    println!("{:.7} {}", result.id, result.subject);
+    for violation in &result.violations {
+      println!("  {violation}");
+    }
+    if args.suggest {
+      let commit = repo.find_commit(result.id)?;
+      let suggestion = lint::suggest(&repo, &commit, &rules, model.clone()).await?;
+      println!("  suggestion: {suggestion}");
+    }
+    println!();
+  }
+
+  if !failing.is_empty() {
+    bail!("{} of {} commits violate the commit message rules", failing.len(), results.len());
+  }
+
+  println!("✅ {} commits pass the commit message rules", results.len());
+  Ok(())
+}
+
+async fn run_stash(args: StashArgs) -> Result<()> {
+  let mut repo = Repository::open_from_env().context("Failed to open repository")?;
+  let model = resolve_model(match &args.command {
+    Some(StashSubcommand::List { model }) => model.clone(),
+    None => args.model
+  });
+
+  if args.command.is_none() {
+    let message = stash::generate_message(&repo, model, None).await?;
+    stash::save(&mut repo, &message)?;
+    println!("✅ Saved working directory and index state: {message}");
+    return Ok(());
+  }
+
+  let entries = stash::list(&mut repo)?;
+  if entries.is_empty() {
+    println!("No stashes");
+    return Ok(());
+  }
+
+  for entry in &entries {
+    println!("stash@{{{}}}: {}", entry.index, entry.message);
+    if entry.is_unnamed() {
+      println!("    → {}", stash::summarize(&repo, entry, model.clone()).await?);
+    }
+  }
+
+  Ok(())
+}
+
+fn run_tag(args: TagArgs) -> Result<()> {
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let release = tag::prepare(&repo, args.name.as_deref())?;
+
+  print!("{}", release.message);
+  match &release.previous {
+    Some(previous) => println!("\n{} commits since {previous}, a {} release", release.commits, release.bump),
+    None => println!("\n{} commits, the first release", release.commits)
+  }
+
+  let prompt = format!("Create annotated tag {} on {:.7}?", release.name, release.target);
+  if !args.yes && !confirm(&prompt)? {
+    println!("Nothing tagged");
+    return Ok(());
+  }
+
+  tag::create(&repo, &release)?;
+  println!("✅ Tagged {}; push it with `git push origin {}`", release.name, release.name);
+  Ok(())
+}
+
+async fn run_fixup(args: FixupArgs) -> Result<()> {
+  let repo = Repository::open_from_env().context("Failed to open repository")?;
+  let model = resolve_model(args.model);
+
+  if !args.dry_run {
+    // Fail before generating messages rather than after
+    repo
+      .signature()
+      .context("Failed to determine the committer; set user.name and user.email")?;
+  }
+
+  let planned = fixup::plan(&repo, model).await?;
+
+  println!("Proposed commits:");
+  for (i, fixup) in planned.iter().enumerate() {
+    let target = match fixup.target {
+      Some(target) => format!(" (amends {target:.7})"),
+      None => " (no single unpushed commit to amend)".to_string()
+    };
+    println!("\n{}. {}{target}", i + 1, fixup.commit.message.lines().next().unwrap_or_default());
+    for (path, added, removed) in fixup.commit.group.files() {
+      println!("     {} (+{added} -{removed})", path.display());
+    }
+  }
+  println!();
+
+  if args.dry_run {
+    println!("Dry run, nothing was committed");
+    return Ok(());
+  }
+
+  if !args.yes && !confirm(&format!("Create {} commits?", planned.len()))? {
+    println!("Aborted, nothing was committed");
+    return Ok(());
+  }
+
+  let commits: Vec<split::PlannedCommit> = planned.iter().map(|fixup| fixup.commit.clone()).collect();
+  split::apply(&repo, &commits)?;
+  println!("✅ Created {} commits", commits.len());
+
+  if planned.iter().any(|fixup| fixup.target.is_some()) {
+    let onto = match fixup::rebase_base(&repo, &planned)? {
+      Some(base) => format!("{base:.7}"),
+      None => "--root".to_string()
+    };
+    println!("Fold them in with `git rebase -i --autosquash {onto}`");
+  }
+
+  Ok(())
+}
+
+fn run_usage(args: UsageArgs) -> Result<()> {
+  let ledger = usage::ledger_path();
+  let mut records = usage::read_ledger(&ledger)?;
+  if let Some(days) = args.days {
+    let now = std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)?
+      .as_secs();
+    let since = now.saturating_sub(days * 86_400) as i64;
+    records.retain(|record| record.time >= since);
+  }
+
+  if records.is_empty() {
+    println!("No usage recorded in {}", ledger.display());
+    return Ok(());
+  }
+
+  let prices = usage::load_prices(&usage::prices_path())?;
+  print!("{}", usage::report(&records, args.by, &prices));
+  Ok(())
+}
+
+async fn run_serve() -> Result<()> {
+  serve::serve(tokio::io::BufReader::new(tokio::io::stdin()), tokio::io::stdout()).await
+}
+
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<()> {
   // Load environment variables from .env file if present
@@ -181,7 +1157,8 @@ async fn main() -> Result<()> {
       std::env::set_var("RUST_LOG", "debug");
     }
     env_logger::init();
-    println!("Debug build: Performance profiling enabled");
+    // On stderr, stdout carries command output such as `git ai serve` responses
+    eprintln!("Debug build: Performance profiling enabled");
   }
 
   let args = Cli::from_args();
@@ -205,25 +1182,120 @@ async fn main() -> Result<()> {
           run_config_reset()?;
         }
 
-        ConfigSubcommand::Set(set) =>
+        ConfigSubcommand::Get { key } => {
+          run_config_get(key)?;
+        }
+
+        ConfigSubcommand::List => {
+          run_config_list()?;
+        }
+
+        ConfigSubcommand::Unset { key, local, profile } => {
+          run_config_unset(key, &config_scope(local, profile))?;
+        }
+
+        ConfigSubcommand::Set { local, profile, set } => {
+          let scope = config_scope(local, profile);
           match set {
             SetSubcommand::Model(model) => {
-              run_config_model(model.value).await?;
+              run_config_model(model.value, &scope).await?;
             }
             SetSubcommand::MaxTokens { max_tokens } => {
-              run_config_max_tokens(max_tokens)?;
+              run_config_max_tokens(max_tokens, &scope)?;
             }
             SetSubcommand::MaxCommitLength { max_commit_length } => {
-              run_config_max_commit_length(max_commit_length)?;
+              run_config_max_commit_length(max_commit_length, &scope)?;
             }
             SetSubcommand::OpenaiApiKey { value } => {
-              run_config_openai_api_key(value)?;
+              run_config_openai_api_key(value, &scope)?;
+            }
+            SetSubcommand::OpenaiApiKeyCommand { value } => {
+              run_config_api_key_source(ConfigKey::OpenaiApiKeyCommand, value, &scope)?;
+            }
+            SetSubcommand::OpenaiApiKeyFile { value } => {
+              run_config_api_key_source(ConfigKey::OpenaiApiKeyFile, value, &scope)?;
+            }
+            SetSubcommand::OpenaiApiKeyEnv { value } => {
+              run_config_api_key_source(ConfigKey::OpenaiApiKeyEnv, value, &scope)?;
+            }
+            SetSubcommand::ForbidPlaintextApiKey { value } => {
+              run_config_forbid_plaintext_api_key(value, &scope)?;
             }
             SetSubcommand::OpenaiBaseUrl { value } => {
-              run_config_openai_base_url(value)?;
+              run_config_openai_base_url(value, &scope)?;
+            }
+            SetSubcommand::Timeout { timeout } => {
+              run_config_timeout(timeout, &scope)?;
             }
-          },
+            SetSubcommand::BranchPattern { value } => {
+              run_config_branch_pattern(value, &scope)?;
+            }
+            SetSubcommand::ConventionalCommits { value } => {
+              run_config_conventional_commits(value, &scope)?;
+            }
+            SetSubcommand::Language { value } => {
+              run_config_language(value, &scope)?;
+            }
+          }
+        }
+      },
+    Cli::Profile(profile) =>
+      match profile {
+        ProfileSubcommand::Use { name } => {
+          run_profile_use(&name)?;
+        }
+        ProfileSubcommand::List => {
+          run_profile_list()?;
+        }
       },
+    Cli::Generate(args) => {
+      run_generate(args).await?;
+    }
+    Cli::Explain(args) => {
+      run_explain(args).await?;
+    }
+    Cli::Reword(args) => {
+      run_reword(args).await?;
+    }
+    Cli::Pr(args) => {
+      run_pr(args).await?;
+    }
+    Cli::Changelog(args) => {
+      run_changelog(args)?;
+    }
+    Cli::Doctor => {
+      run_doctor().await?;
+    }
+    Cli::Models(args) => {
+      run_models(args).await?;
+    }
+    Cli::Split(args) => {
+      run_split(args).await?;
+    }
+    Cli::Review(args) => {
+      run_review(args).await?;
+    }
+    Cli::Branch(args) => {
+      run_branch(args).await?;
+    }
+    Cli::Lint(args) => {
+      run_lint(args).await?;
+    }
+    Cli::Stash(args) => {
+      run_stash(args).await?;
+    }
+    Cli::Tag(args) => {
+      run_tag(args)?;
+    }
+    Cli::Fixup(args) => {
+      run_fixup(args).await?;
+    }
+    Cli::Usage(args) => {
+      run_usage(args)?;
+    }
+    Cli::Serve => {
+      run_serve().await?;
+    }
   }
 
   Ok(())
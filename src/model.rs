@@ -77,6 +77,15 @@ impl Model {
     get_context_size(self.as_ref()).unwrap_or(4096)
   }
 
+  /// Gets the context size when it is actually known for the model, without the
+  /// 4096 fallback [`Model::context_size`] uses for unrecognized models.
+  ///
+  /// # Returns
+  /// * `Option<usize>` - The maximum number of tokens, or None for unknown models
+  pub fn known_context_size(&self) -> Option<usize> {
+    get_context_size(self.as_ref())
+  }
+
   /// Truncates the given text to fit within the specified token limit.
   ///
   /// # Arguments
@@ -85,7 +94,7 @@ impl Model {
   ///
   /// # Returns
   /// * `Result<String>` - The truncated text or an error
-  pub(crate) fn truncate(&self, text: &str, max_tokens: usize) -> Result<String> {
+  pub fn truncate(&self, text: &str, max_tokens: usize) -> Result<String> {
     profile!("Truncate text");
     self.walk_truncate(text, max_tokens, usize::MAX)
   }
@@ -274,6 +283,14 @@ mod tests {
     assert_eq!(truncated, text);
   }
 
+  /// Only unknown models fall back to the default context size.
+  #[test]
+  fn test_known_context_size_has_no_fallback() {
+    assert_eq!(Model::GPT41Mini.known_context_size(), Some(Model::GPT41Mini.context_size()));
+    assert_eq!(Model::Other("llama3".to_string()).known_context_size(), None);
+    assert_eq!(Model::Other("llama3".to_string()).context_size(), 4096);
+  }
+
   /// max_tokens == 0 yields an empty string (and never panics).
   #[test]
   fn test_truncate_zero_tokens() {
@@ -8,10 +8,11 @@ use serde_json::Value;
 use futures::future::join_all;
 
 use crate::multi_step_analysis::{
-  create_analyze_function_tool, create_generate_function_tool, create_score_function_tool, FileDataForScoring, FileWithScore
+  create_analyze_function_tool, create_generate_function_tool, create_review_function_tool, create_score_function_tool, review_file, FileDataForScoring, FileWithScore, ReviewFinding
 };
 use crate::function_calling::{create_commit_function_tool, CommitFunctionArgs};
-use crate::debug_output;
+use crate::generation::{CommitResponse, Strategy};
+use crate::{debug_output, language, usage};
 
 /// System prompt for the `analyze` step. Drives per-file analysis that feeds the
 /// `analyze` function-calling tool. Kept as a `pub const` so the prompt contract can be
@@ -47,6 +48,14 @@ use no trailing period, and lead with the change of highest impact. \
 Describe what changed and why, not how; do not invent changes absent from the diff. \
 Return the final message only through the supplied commit function.";
 
+/// System prompt for the `review` step (pre-commit review of the staged diff).
+pub const REVIEW_SYSTEM_PROMPT: &str = "You are a senior engineer reviewing staged changes right before they are committed. \
+Look only at added lines and report leaked secrets (keys, tokens, passwords), debug leftovers (print debugging, \
+breakpoints, commented-out code) and obvious bugs (wrong conditions, unhandled errors, off-by-one mistakes). \
+Use line numbers of the new file version taken from the hunk headers. Use high severity only for secrets and \
+definite bugs. Do not report style preferences or speculate about code that is not in the diff; report no findings \
+when there is nothing wrong. Return the findings only through the supplied function.";
+
 /// Represents a parsed file from the git diff
 #[derive(Debug)]
 pub struct ParsedFile {
@@ -83,8 +92,19 @@ fn analysis_str(analysis: &Value, field: &str, default: &str) -> String {
 
 /// Main entry point for multi-step commit message generation
 pub async fn generate_commit_message_multi_step(
-  client: &Client<OpenAIConfig>, model: &str, diff_content: &str, max_length: Option<usize>
+  client: &Client<OpenAIConfig>, model: &str, diff_content: &str, max_length: Option<usize>, language: &str
 ) -> Result<String> {
+  generate_commit_response_multi_step(client, model, diff_content, max_length, language)
+    .await
+    .map(|response| response.message)
+}
+
+/// Runs the multi-step pipeline and returns the final message together with the
+/// reasoning, the generated candidates and the per-file scores it was based on. Summaries,
+/// candidates and the message are written in `language`.
+pub async fn generate_commit_response_multi_step(
+  client: &Client<OpenAIConfig>, model: &str, diff_content: &str, max_length: Option<usize>, language: &str
+) -> Result<CommitResponse> {
   log::info!("Starting multi-step commit message generation");
 
   // Initialize multi-step debug session
@@ -115,7 +135,7 @@ pub async fn generate_commit_message_multi_step(
         let start_time = std::time::Instant::now();
         let payload = format!("{{\"file_path\": \"{file_path}\", \"operation_type\": \"{operation}\", \"diff_content\": \"...\"}}");
 
-        let result = call_analyze_function(client, model, file).await;
+        let result = call_analyze_function(client, model, file, language).await;
         let duration = start_time.elapsed();
         (file, result, duration, payload)
       }
@@ -191,7 +211,7 @@ pub async fn generate_commit_message_multi_step(
 
   // Start step 2 and 3 in parallel
   // First create the futures for both operations
-  let score_future = call_score_function(client, model, files_data);
+  let score_future = call_score_function(client, model, files_data, language);
 
   // Run the scoring operation
   let scored_files = score_future.await?;
@@ -207,7 +227,7 @@ pub async fn generate_commit_message_multi_step(
   let generate_payload = format!("{{\"files_with_scores\": [...], \"max_length\": {}}}", max_length.unwrap_or(72));
 
   // Now create and run the generate and select steps in parallel
-  let generate_future = call_generate_function(client, model, scored_files.clone(), max_length.unwrap_or(72));
+  let generate_future = call_generate_function(client, model, scored_files.clone(), max_length.unwrap_or(72), language);
 
   let candidates = generate_future.await?;
   let generate_duration = generate_start_time.elapsed();
@@ -219,16 +239,43 @@ pub async fn generate_commit_message_multi_step(
 
   // Step 4: Select the best candidate and format final response
   let final_message_start_time = std::time::Instant::now();
-  let final_message = select_best_candidate(client, model, &candidates, &scored_files, diff_content, max_length.unwrap_or(72)).await?;
+  let final_message = select_best_candidate(
+    client,
+    model,
+    &candidates,
+    &scored_files,
+    diff_content,
+    max_length.unwrap_or(72),
+    language
+  )
+  .await?;
   let final_message_duration = final_message_start_time.elapsed();
 
+  let reasoning = candidates["reasoning"].as_str().unwrap_or("").to_string();
+
   // Record in debug session
   if let Some(session) = debug_output::debug_session() {
     session.set_final_message_debug(final_message_duration);
-    session.set_commit_result(final_message.clone(), candidates["reasoning"].as_str().unwrap_or("").to_string());
+    session.set_commit_result(final_message.clone(), reasoning.clone());
   }
 
-  Ok(final_message)
+  let candidate_messages: Vec<String> = candidates["candidates"]
+    .as_array()
+    .map(|values| {
+      values
+        .iter()
+        .filter_map(|value| value.as_str().map(str::to_string))
+        .collect()
+    })
+    .unwrap_or_default();
+
+  Ok(CommitResponse::from_scored_files(
+    final_message,
+    reasoning,
+    candidate_messages,
+    &scored_files,
+    Strategy::MultiStep
+  ))
 }
 
 /// Extracts the file path from git diff header parts.
@@ -458,11 +505,11 @@ pub fn parse_diff(diff_content: &str) -> Result<Vec<ParsedFile>> {
 }
 
 /// Call the analyze function via OpenAI
-async fn call_analyze_function(client: &Client<OpenAIConfig>, model: &str, file: &ParsedFile) -> Result<Value> {
+async fn call_analyze_function(client: &Client<OpenAIConfig>, model: &str, file: &ParsedFile, language: &str) -> Result<Value> {
   let tools = vec![ChatCompletionTools::Function(create_analyze_function_tool()?)];
 
   let system_message = ChatCompletionRequestSystemMessageArgs::default()
-    .content(ANALYZE_SYSTEM_PROMPT)
+    .content(language::system_prompt(ANALYZE_SYSTEM_PROMPT, language))
     .build()?
     .into();
 
@@ -482,6 +529,7 @@ async fn call_analyze_function(client: &Client<OpenAIConfig>, model: &str, file:
     .build()?;
 
   let response = client.chat().create(request).await?;
+  usage::record("analyze", model, response.usage.as_ref());
 
   if let Some(arguments) = first_function_call_arguments(&response) {
     let args: Value = serde_json::from_str(arguments)?;
@@ -510,12 +558,12 @@ fn first_function_call_arguments(response: &async_openai::types::chat::CreateCha
 
 /// Call the score function via OpenAI
 async fn call_score_function(
-  client: &Client<OpenAIConfig>, model: &str, files_data: Vec<FileDataForScoring>
+  client: &Client<OpenAIConfig>, model: &str, files_data: Vec<FileDataForScoring>, language: &str
 ) -> Result<Vec<FileWithScore>> {
   let tools = vec![ChatCompletionTools::Function(create_score_function_tool()?)];
 
   let system_message = ChatCompletionRequestSystemMessageArgs::default()
-    .content(SCORE_SYSTEM_PROMPT)
+    .content(language::system_prompt(SCORE_SYSTEM_PROMPT, language))
     .build()?
     .into();
 
@@ -536,6 +584,7 @@ async fn call_score_function(
     .build()?;
 
   let response = client.chat().create(request).await?;
+  usage::record("score", model, response.usage.as_ref());
 
   if let Some(arguments) = first_function_call_arguments(&response) {
     let args: Value = serde_json::from_str(arguments)?;
@@ -552,12 +601,12 @@ async fn call_score_function(
 
 /// Call the generate function via OpenAI
 async fn call_generate_function(
-  client: &Client<OpenAIConfig>, model: &str, files_with_scores: Vec<FileWithScore>, max_length: usize
+  client: &Client<OpenAIConfig>, model: &str, files_with_scores: Vec<FileWithScore>, max_length: usize, language: &str
 ) -> Result<Value> {
   let tools = vec![ChatCompletionTools::Function(create_generate_function_tool()?)];
 
   let system_message = ChatCompletionRequestSystemMessageArgs::default()
-    .content(GENERATE_SYSTEM_PROMPT)
+    .content(language::system_prompt(GENERATE_SYSTEM_PROMPT, language))
     .build()?
     .into();
 
@@ -578,6 +627,7 @@ async fn call_generate_function(
     .build()?;
 
   let response = client.chat().create(request).await?;
+  usage::record("generate", model, response.usage.as_ref());
 
   if let Some(arguments) = first_function_call_arguments(&response) {
     let args: Value = serde_json::from_str(arguments)?;
@@ -589,14 +639,15 @@ async fn call_generate_function(
 
 /// Select the best candidate and format the final response
 async fn select_best_candidate(
-  client: &Client<OpenAIConfig>, model: &str, candidates: &Value, scored_files: &[FileWithScore], original_diff: &str, max_length: usize
+  client: &Client<OpenAIConfig>, model: &str, candidates: &Value, scored_files: &[FileWithScore], original_diff: &str, max_length: usize,
+  language: &str
 ) -> Result<String> {
   // Use the original commit function to get the final formatted response,
   // honoring the configured commit-length limit (was previously hardcoded to 72).
   let tools = vec![ChatCompletionTools::Function(create_commit_function_tool(Some(max_length))?)];
 
   let system_message = ChatCompletionRequestSystemMessageArgs::default()
-    .content(COMMIT_SYSTEM_PROMPT)
+    .content(language::system_prompt(COMMIT_SYSTEM_PROMPT, language))
     .build()?
     .into();
 
@@ -624,6 +675,7 @@ async fn select_best_candidate(
     .build()?;
 
   let response = client.chat().create(request).await?;
+  usage::record("select", model, response.usage.as_ref());
 
   if let Some(arguments) = first_function_call_arguments(&response) {
     // First, parse as Value to manually handle required fields
@@ -643,11 +695,104 @@ async fn select_best_candidate(
 }
 
 /// Alternative: Use the multi-step analysis locally without OpenAI calls
-pub fn generate_commit_message_local(diff_content: &str, max_length: Option<usize>) -> Result<String> {
-  use crate::multi_step_analysis::{analyze_file, calculate_impact_scores, generate_commit_messages};
+pub fn generate_commit_message_local(diff_content: &str, max_length: Option<usize>, language: &str) -> Result<String> {
+  generate_commit_response_local(diff_content, max_length, language).map(|response| response.message)
+}
+
+/// Local multi-step analysis that keeps the candidates, reasoning and per-file scores
+pub fn generate_commit_response_local(diff_content: &str, max_length: Option<usize>, language: &str) -> Result<CommitResponse> {
+  use crate::multi_step_analysis::generate_commit_messages;
 
   log::info!("Starting local multi-step commit message generation");
 
+  // Steps 1 and 2: Analyze and score each file
+  let files_with_scores = score_files_local(diff_content)?;
+
+  // Step 3: Generate candidates
+  let generate_result = generate_commit_messages(files_with_scores.clone(), max_length.unwrap_or(72), language);
+
+  // Return the first candidate. Keep a safe fallback, but surface the failure so a
+  // silent "Update files" message is never mistaken for a real generated message.
+  let message = match generate_result.candidates.first() {
+    Some(candidate) => candidate.clone(),
+    None => {
+      log::warn!("Local multi-step generation produced no candidates; falling back to 'Update files'");
+      "Update files".to_string()
+    }
+  };
+
+  Ok(CommitResponse::from_scored_files(
+    message,
+    generate_result.reasoning,
+    generate_result.candidates,
+    &files_with_scores,
+    Strategy::Local
+  ))
+}
+
+/// Reviews the diff through the `review` function tool. Findings for files that are
+/// not part of the diff are dropped, since the model has nothing to base them on.
+pub async fn review_with_api(client: &Client<OpenAIConfig>, model: &str, diff_content: &str) -> Result<Vec<ReviewFinding>> {
+  let tools = vec![ChatCompletionTools::Function(create_review_function_tool()?)];
+
+  let system_message = ChatCompletionRequestSystemMessageArgs::default()
+    .content(REVIEW_SYSTEM_PROMPT)
+    .build()?
+    .into();
+
+  let user_message = ChatCompletionRequestUserMessageArgs::default()
+    .content(format!("Review these staged changes:\n\n{diff_content}"))
+    .build()?
+    .into();
+
+  let request = CreateChatCompletionRequestArgs::default()
+    .model(model)
+    .messages(vec![system_message, user_message])
+    .tools(tools)
+    .tool_choice(ChatCompletionToolChoiceOption::Function(ChatCompletionNamedToolChoice::from("review")))
+    .build()?;
+
+  let response = client.chat().create(request).await?;
+  usage::record("review", model, response.usage.as_ref());
+
+  if let Some(arguments) = first_function_call_arguments(&response) {
+    let args: Value = serde_json::from_str(arguments)?;
+    let findings: Vec<ReviewFinding> = if args["findings"].is_null() {
+      Vec::new()
+    } else {
+      serde_json::from_value(args["findings"].clone())?
+    };
+
+    let paths: Vec<String> = parse_diff(diff_content)?
+      .into_iter()
+      .map(|file| file.path)
+      .collect();
+    Ok(
+      findings
+        .into_iter()
+        .filter(|finding| paths.contains(&finding.file_path))
+        .collect()
+    )
+  } else {
+    anyhow::bail!("No tool call in response")
+  }
+}
+
+/// Reviews every file in the diff locally (no API calls)
+pub fn review_local(diff_content: &str) -> Result<Vec<ReviewFinding>> {
+  Ok(
+    parse_diff(diff_content)?
+      .iter()
+      .flat_map(|file| review_file(&file.path, &file.diff_content))
+      .collect()
+  )
+}
+
+/// Parses, analyzes and scores every file in the diff locally (no API calls).
+/// The result is sorted by impact score, highest first.
+pub fn score_files_local(diff_content: &str) -> Result<Vec<FileWithScore>> {
+  use crate::multi_step_analysis::{analyze_file, calculate_impact_scores};
+
   // Parse the diff
   let parsed_files = parse_diff(diff_content)?;
 
@@ -656,7 +801,7 @@ pub fn generate_commit_message_local(diff_content: &str, max_length: Option<usiz
     session.set_total_files_parsed(parsed_files.len());
   }
 
-  // Step 1: Analyze each file
+  // Analyze each file
   let mut files_data = Vec::new();
   for file in parsed_files {
     let analysis = analyze_file(&file.path, &file.diff_content, &file.operation);
@@ -670,21 +815,8 @@ pub fn generate_commit_message_local(diff_content: &str, max_length: Option<usiz
     });
   }
 
-  // Step 2: Calculate scores
-  let score_result = calculate_impact_scores(files_data);
-
-  // Step 3: Generate candidates
-  let generate_result = generate_commit_messages(score_result.files_with_scores, max_length.unwrap_or(72));
-
-  // Return the first candidate. Keep a safe fallback, but surface the failure so a
-  // silent "Update files" message is never mistaken for a real generated message.
-  match generate_result.candidates.first() {
-    Some(candidate) => Ok(candidate.clone()),
-    None => {
-      log::warn!("Local multi-step generation produced no candidates; falling back to 'Update files'");
-      Ok("Update files".to_string())
-    }
-  }
+  // Calculate scores
+  Ok(calculate_impact_scores(files_data).files_with_scores)
 }
 
 #[cfg(test)]
@@ -874,8 +1006,56 @@ index 1234567..abcdefg 100644
 +    }
  }"#;
 
-    let message = generate_commit_message_local(diff, Some(72)).unwrap();
+    let message = generate_commit_message_local(diff, Some(72), "en").unwrap();
     assert!(!message.is_empty());
     assert!(message.len() <= 72);
   }
+
+  #[test]
+  fn test_local_response_keeps_candidates_and_files() {
+    let diff = r#"diff --git a/src/auth.rs b/src/auth.rs
+new file mode 100644
+index 0000000..1234567
+--- /dev/null
++++ b/src/auth.rs
+@@ -0,0 +1,2 @@
++pub fn authenticate() {}
++pub fn logout() {}
+"#;
+
+    let response = generate_commit_response_local(diff, Some(72), "en").unwrap();
+    assert_eq!(response.strategy, Strategy::Local);
+    assert_eq!(response.candidates.first(), Some(&response.message));
+    assert!(!response.reasoning.is_empty());
+
+    let file = response.files.get("src/auth.rs").expect("per-file summary");
+    assert_eq!(file.lines_added, 2);
+
+    // The JSON shape consumed by `git ai generate --json`
+    let json = serde_json::to_value(&response).unwrap();
+    assert_eq!(json["strategy"], "local");
+    assert_eq!(json["files"]["src/auth.rs"]["operation_type"], "Added");
+  }
+
+  #[test]
+  fn test_local_candidates_follow_the_language() {
+    let diff = r#"diff --git a/src/auth.rs b/src/auth.rs
+new file mode 100644
+index 0000000..1234567
+--- /dev/null
++++ b/src/auth.rs
+@@ -0,0 +1 @@
++pub fn authenticate() {}
+"#;
+
+    let english = generate_commit_response_local(diff, Some(72), "en").unwrap();
+    assert_eq!(english.candidates, vec!["Add auth", "auth: implementation", "New feature for auth"]);
+
+    let swedish = generate_commit_response_local(diff, Some(72), "sv").unwrap();
+    assert_eq!(swedish.message, "Lägg till auth");
+    assert_eq!(swedish.candidates[2], "Ny funktion för auth");
+
+    let german = generate_commit_response_local(diff, Some(72), "de-AT").unwrap();
+    assert_eq!(german.message, "auth hinzufügen");
+  }
 }
@@ -10,7 +10,7 @@ use anyhow::{anyhow, Context, Result};
 use reqwest;
 use futures::future::join_all;
 
-use crate::{commit, config, debug_output, function_calling, profile};
+use crate::{commit, config, debug_output, function_calling, profile, usage};
 use crate::model::Model;
 use crate::config::AppConfig;
 use crate::multi_step_integration::generate_commit_message_multi_step;
@@ -120,19 +120,16 @@ pub fn create_openai_config(settings: &AppConfig) -> Result<OpenAIConfig> {
     .map(str::trim)
     .filter(|s| !s.is_empty());
 
-  let api_key = settings.openai_api_key.as_deref().unwrap_or("").trim();
-  let key_missing = api_key.is_empty() || api_key == "<PLACE HOLDER FOR YOUR API KEY>";
-
   // A custom endpoint (e.g. a local ollama `/v1` server) usually needs no real key, so
   // supply a placeholder when one isn't configured. The default OpenAI endpoint still
   // requires a real key.
-  let effective_key = if key_missing {
-    match base_url {
-      Some(_) => "sk-no-key-required",
-      None => return Err(anyhow!("OpenAI API key not configured"))
-    }
-  } else {
-    api_key
+  let effective_key = match settings.api_key()? {
+    Some(api_key) => api_key,
+    None =>
+      match base_url {
+        Some(_) => "sk-no-key-required".to_string(),
+        None => return Err(anyhow!("OpenAI API key not configured"))
+      },
   };
 
   let mut config = OpenAIConfig::new().with_api_key(effective_key);
@@ -214,6 +211,77 @@ pub async fn verify_model_exists(settings: &AppConfig, candidate: &str, known_or
   }
 }
 
+/// Builds a client for `settings`, honoring the configured request timeout
+pub fn client_for(settings: &AppConfig) -> Result<Client<OpenAIConfig>> {
+  let config = create_openai_config(settings)?;
+  Ok(match settings.timeout {
+    Some(timeout) => {
+      let http_client = reqwest::ClientBuilder::new()
+        .timeout(Duration::from_secs(timeout as u64))
+        .build()?;
+      Client::with_config(config).with_http_client(http_client)
+    }
+    None => Client::with_config(config)
+  })
+}
+
+/// Lists the ids of the models the configured endpoint offers.
+///
+/// Request failures keep their [`OpenAIError`] as the error source, so callers can
+/// `downcast_ref` it to tell an unreachable endpoint from a rejected key.
+pub async fn list_models(settings: &AppConfig) -> Result<Vec<String>> {
+  let client = client_for(settings)?;
+  let list = client.models().list().await?;
+  Ok(list.data.into_iter().map(|m| m.id).collect())
+}
+
+/// Checks whether `model` honors a forced call of the `commit` tool, which the
+/// generation pipeline depends on. `Ok(false)` means the model answered without
+/// calling the tool.
+pub async fn supports_forced_tool_calls(settings: &AppConfig, model: &str) -> Result<bool> {
+  let client = client_for(settings)?;
+  let commit_tool = function_calling::create_commit_function_tool(settings.max_commit_length)?;
+
+  let request = CreateChatCompletionRequestArgs::default()
+    .model(model)
+    .max_completion_tokens(256_u32)
+    .messages([ChatCompletionRequestUserMessageArgs::default()
+      .content("diff --git a/README.md b/README.md\n+Hello world")
+      .build()?
+      .into()])
+    .tools(vec![ChatCompletionTools::Function(commit_tool)])
+    .tool_choice(ChatCompletionToolChoiceOption::Function(ChatCompletionNamedToolChoice::from("commit")))
+    .build()?;
+
+  let response = client.chat().create(request).await?;
+  usage::record("probe", model, response.usage.as_ref());
+  let called = response.choices.first().is_some_and(|choice| {
+    choice.message.tool_calls.iter().flatten().any(
+      |call| matches!(call, async_openai::types::chat::ChatCompletionMessageToolCalls::Function(call) if call.function.name == "commit")
+    )
+  });
+
+  Ok(called)
+}
+
+/// Returns `settings` with the `OPENAI_API_KEY` environment variable as the key when no
+/// usable key is configured, mirroring the fallback generation uses without settings.
+/// Key sources are not read here; that waits until a request is made.
+pub fn with_env_api_key(mut settings: AppConfig) -> AppConfig {
+  let custom_endpoint = settings
+    .openai_base_url
+    .as_deref()
+    .is_some_and(|url| !url.trim().is_empty());
+  if !settings.has_api_key() && !custom_endpoint {
+    if let Ok(key) = std::env::var("OPENAI_API_KEY") {
+      if !key.is_empty() {
+        settings.openai_api_key = Some(key);
+      }
+    }
+  }
+  settings
+}
+
 /// Truncates text to fit within token limits
 fn truncate_to_fit(text: &str, max_tokens: usize, model: &Model) -> Result<String> {
   profile!("Truncate to fit");
@@ -294,7 +362,11 @@ pub async fn call_with_config(request: Request, config: OpenAIConfig) -> Result<
   let client = Client::with_config(config.clone());
   let model = request.model.to_string();
 
-  match generate_commit_message_multi_step(&client, &model, &request.prompt, config::APP_CONFIG.max_commit_length).await {
+  let language = config::APP_CONFIG
+    .language
+    .as_deref()
+    .unwrap_or(config::DEFAULT_LANGUAGE);
+  match generate_commit_message_multi_step(&client, &model, &request.prompt, config::APP_CONFIG.max_commit_length, language).await {
     Ok(message) => return Ok(Response { response: message }),
     Err(e) => {
       // Check if it's an API key error and propagate it
@@ -362,6 +434,7 @@ pub async fn call_with_config(request: Request, config: OpenAIConfig) -> Result<
         }
 
         log::debug!("OpenAI API call successful on attempt {attempt}");
+        usage::record("single-step", request.model.as_ref(), response.usage.as_ref());
 
         // Extract the response
         let choice = response
@@ -488,12 +561,13 @@ mod tests {
 
   fn settings_with(api_key: Option<&str>, base_url: Option<&str>) -> AppConfig {
     AppConfig {
-      openai_api_key:    api_key.map(|s| s.to_string()),
-      openai_base_url:   base_url.map(|s| s.to_string()),
-      model:             Some("gpt-4.1-mini".to_string()),
-      max_tokens:        Some(1024),
+      openai_api_key: api_key.map(|s| s.to_string()),
+      openai_base_url: base_url.map(|s| s.to_string()),
+      model: Some("gpt-4.1-mini".to_string()),
+      max_tokens: Some(1024),
       max_commit_length: Some(72),
-      timeout:           Some(30)
+      timeout: Some(30),
+      ..Default::default()
     }
   }
 
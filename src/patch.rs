@@ -0,0 +1,239 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// A single patch read from a file or stdin, with its diff in the form the hook produces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+  /// Where the patch came from, e.g. `0001-add-parser.patch` or `stdin 2/3`
+  pub name: String,
+  pub diff: String
+}
+
+/// Extended header lines that belong to a `diff --git` block
+const GIT_HEADERS: [&str; 12] = [
+  "old mode ", "new mode ", "deleted file mode ", "new file mode ", "copy from ", "copy to ", "rename from ", "rename to ",
+  "similarity index ", "dissimilarity index ", "index ", "Binary files "
+];
+
+/// Whether `line` starts a message in a `git format-patch` mailbox:
+/// `From <40 hex digits> Mon Sep 17 00:00:00 2001`
+fn is_mbox_separator(line: &str) -> bool {
+  line
+    .strip_prefix("From ")
+    .and_then(|rest| rest.get(..41))
+    .is_some_and(|hash| hash.ends_with(' ') && hash[..40].chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// The path of a `---` or `+++` line without the timestamp or revision some tools append
+/// after a tab, and without the `a/` or `b/` prefix
+fn header_path<'a>(line: &'a str, prefix: &str) -> &'a str {
+  let path = line[4..].split('\t').next().unwrap_or_default().trim_end();
+  path.strip_prefix(prefix).unwrap_or(path)
+}
+
+/// The old and new line counts of a `@@ -a,b +c,d @@` hunk header; a missing count is 1
+fn hunk_lengths(header: &str) -> Option<(usize, usize)> {
+  let mut ranges = header.strip_prefix("@@ ")?.split_whitespace();
+  let mut length = |sign: char| -> Option<usize> {
+    let range = ranges.next()?.strip_prefix(sign)?;
+    match range.split_once(',') {
+      Some((_, length)) => length.parse().ok(),
+      None => Some(1)
+    }
+  };
+  Some((length('-')?, length('+')?))
+}
+
+/// Rewrites a unified diff from any tool into the `diff --git` form the analysis expects.
+/// Git diffs keep their extended headers; plain `diff -u`, Subversion and Mercurial diffs
+/// get a `diff --git` line per file, with `new file` or `deleted file` modes for
+/// `/dev/null` sides. Commit messages, diffstats, `Index:` lines and signatures around
+/// the file diffs are dropped.
+pub fn normalize(text: &str) -> String {
+  let mut out = String::new();
+  let mut push = |line: &str| {
+    out.push_str(line);
+    out.push('\n');
+  };
+
+  let mut lines = text
+    .lines()
+    .map(|line| line.strip_suffix('\r').unwrap_or(line))
+    .peekable();
+  let mut in_git_header = false;
+
+  while let Some(line) = lines.next() {
+    if line.starts_with("diff --git ") {
+      push(line);
+      in_git_header = true;
+    } else if in_git_header && GIT_HEADERS.iter().any(|header| line.starts_with(header)) {
+      push(line);
+    } else if line.starts_with("--- ") && lines.peek().is_some_and(|next| next.starts_with("+++ ")) {
+      let new_line = lines.next().unwrap_or_default();
+      let (old, new) = (header_path(line, "a/"), header_path(new_line, "b/"));
+      let path = if new == "/dev/null" {
+        old
+      } else {
+        new
+      };
+
+      if !in_git_header {
+        push(&format!("diff --git a/{path} b/{path}"));
+        if old == "/dev/null" {
+          push("new file mode 100644");
+        } else if new == "/dev/null" {
+          push("deleted file mode 100644");
+        }
+      }
+      push(&match old {
+        "/dev/null" => "--- /dev/null".to_string(),
+        old => format!("--- a/{old}")
+      });
+      push(&match new {
+        "/dev/null" => "+++ /dev/null".to_string(),
+        new => format!("+++ b/{new}")
+      });
+      in_git_header = false;
+    } else if let Some((mut old, mut new)) = hunk_lengths(line) {
+      push(line);
+      // Count the hunk body so removed lines starting with `-- ` are not taken for headers
+      while old > 0 || new > 0 {
+        let Some(body) = lines.next() else {
+          break;
+        };
+        match body.chars().next() {
+          Some('-') => old = old.saturating_sub(1),
+          Some('+') => new = new.saturating_sub(1),
+          Some('\\') => {}
+          // Mailers sometimes strip the space of empty context lines
+          _ => {
+            old = old.saturating_sub(1);
+            new = new.saturating_sub(1);
+          }
+        }
+        push(body);
+      }
+      if let Some(marker) = lines.next_if(|next| next.starts_with('\\')) {
+        push(marker);
+      }
+      in_git_header = false;
+    } else {
+      in_git_header = false;
+    }
+  }
+
+  out
+}
+
+/// Splits `content` into its patches: one per message of a `git format-patch` mailbox,
+/// or a single one for anything else. Patches without file changes are left out.
+pub fn parse(name: &str, content: &str) -> Vec<Patch> {
+  let mut messages: Vec<String> = Vec::new();
+  for line in content.lines() {
+    if is_mbox_separator(line) || messages.is_empty() {
+      messages.push(String::new());
+    }
+    if let Some(message) = messages.last_mut() {
+      message.push_str(line);
+      message.push('\n');
+    }
+  }
+
+  let diffs: Vec<String> = messages
+    .iter()
+    .map(|message| normalize(message))
+    .filter(|diff| !diff.is_empty())
+    .collect();
+  let count = diffs.len();
+  diffs
+    .into_iter()
+    .enumerate()
+    .map(|(i, diff)| {
+      let name = if count == 1 {
+        name.to_string()
+      } else {
+        format!("{name} {}/{count}", i + 1)
+      };
+      Patch { name, diff }
+    })
+    .collect()
+}
+
+/// Reads the patches in the file at `path`
+pub fn read(path: &Path) -> Result<Vec<Patch>> {
+  let content = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+  let name = path
+    .file_name()
+    .map_or(path.display().to_string(), |name| name.to_string_lossy().to_string());
+  Ok(parse(&name, &String::from_utf8_lossy(&content)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::multi_step_integration::parse_diff;
+
+  #[test]
+  fn test_normalize_plain_unified_diffs() {
+    let svn = concat!(
+      "Index: src/lib.c\n",
+      "===================================================================\n",
+      "--- src/lib.c\t(revision 12)\n",
+      "+++ src/lib.c\t(working copy)\n",
+      "@@ -1,3 +1,3 @@\n",
+      " int a;\n",
+      "--- removed comment\n",
+      "+int b;\n",
+      "\n",
+      "--- /dev/null\t2024-05-01 10:00:00.000000000 +0200\n",
+      "+++ docs/notes.txt\t2024-05-01 10:00:00.000000000 +0200\n",
+      "@@ -0,0 +1 @@\n",
+      "+notes\n",
+      "\\ No newline at end of file\n"
+    );
+
+    let diff = normalize(svn);
+    assert!(diff.starts_with("diff --git a/src/lib.c b/src/lib.c\n--- a/src/lib.c\n+++ b/src/lib.c\n@@ -1,3 +1,3 @@\n"));
+    assert!(diff.contains(" int a;\n--- removed comment\n+int b;\n\ndiff --git"));
+    assert!(
+      diff.ends_with("new file mode 100644\n--- /dev/null\n+++ b/docs/notes.txt\n@@ -0,0 +1 @@\n+notes\n\\ No newline at end of file\n")
+    );
+    assert!(!diff.contains("Index:"));
+
+    let files = parse_diff(&diff).unwrap();
+    let files: Vec<(&str, &str)> = files
+      .iter()
+      .map(|file| (file.path.as_str(), file.operation.as_str()))
+      .collect();
+    assert_eq!(files, vec![("src/lib.c", "modified"), ("docs/notes.txt", "added")]);
+  }
+
+  #[test]
+  fn test_parse_splits_format_patch_series() {
+    let message = |hash: char, subject: &str, file: &str| {
+      format!(
+        "From {} Mon Sep 17 00:00:00 2001\nFrom: Dev <dev@example.com>\nSubject: [PATCH] {subject}\n\n\
+         Body\n---\n {file} | 1 +\n 1 file changed\n\n\
+         diff --git a/{file} b/{file}\nindex 1111111..2222222 100644\n--- a/{file}\n+++ b/{file}\n\
+         @@ -1 +1,2 @@\n line\n+added\n-- \n2.43.0\n\n",
+        hash.to_string().repeat(40)
+      )
+    };
+    let series = format!(
+      "{}{}",
+      message('a', "Add parser", "src/parser.rs"),
+      message('b', "Add lexer", "src/lexer.rs")
+    );
+
+    let patches = parse("series.mbox", &series);
+    let names: Vec<&str> = patches.iter().map(|patch| patch.name.as_str()).collect();
+    assert_eq!(names, vec!["series.mbox 1/2", "series.mbox 2/2"]);
+    assert_eq!(
+      patches[1].diff,
+      "diff --git a/src/lexer.rs b/src/lexer.rs\nindex 1111111..2222222 100644\n--- a/src/lexer.rs\n+++ b/src/lexer.rs\n@@ -1 +1,2 @@\n line\n+added\n"
+    );
+
+    assert!(parse("empty.patch", "From: Dev\nSubject: nothing\n").is_empty());
+  }
+}
@@ -0,0 +1,209 @@
+use std::fs::OpenOptions;
+
+use anyhow::Result;
+use console::{style, Key, Term};
+
+use crate::generation::CommitResponse;
+
+/// What the user decided in the picker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Choice {
+  /// Use this message, either one of the candidates or an edited version of one
+  Accept(String),
+  /// Throw the candidates away and run the pipeline again
+  Regenerate
+}
+
+/// Whether a picker can be shown. Git runs hooks with stdin redirected, so instead of
+/// stdin this checks that stderr is a terminal and that the controlling terminal can be
+/// opened for key presses. Commits from IDEs and scripts have neither.
+pub fn is_interactive() -> bool {
+  Term::stderr().is_term()
+    && OpenOptions::new()
+      .read(true)
+      .write(true)
+      .open("/dev/tty")
+      .is_ok()
+}
+
+/// The messages to choose from: the selected message first, followed by the remaining
+/// candidates without duplicates or blanks.
+pub fn options(response: &CommitResponse) -> Vec<String> {
+  let mut options: Vec<String> = Vec::new();
+  for message in std::iter::once(&response.message).chain(&response.candidates) {
+    let message = message.trim();
+    if !message.is_empty() && !options.iter().any(|option| option == message) {
+      options.push(message.to_string());
+    }
+  }
+  options
+}
+
+/// Replaces the first line of `message` with `subject`, keeping any body
+fn with_subject(message: &str, subject: &str) -> String {
+  match message.split_once('\n') {
+    Some((_, body)) => format!("{}\n{body}", subject.trim()),
+    None => subject.trim().to_string()
+  }
+}
+
+/// Shortens `text` to `width` characters so every option stays on a single row
+fn fit(text: &str, width: usize) -> String {
+  if text.chars().count() <= width {
+    return text.to_string();
+  }
+  let mut fitted: String = text.chars().take(width.saturating_sub(1)).collect();
+  fitted.push('…');
+  fitted
+}
+
+/// Shows the reasoning and the candidates on stderr and lets the user pick one with the
+/// arrow keys or its number, edit it with `e`, or regenerate with `r`. `q` and `Esc`
+/// keep the message the pipeline selected, which is also accepted without prompting when
+/// there are no candidates to choose from.
+pub fn pick(response: &CommitResponse) -> Result<Choice> {
+  let options = options(response);
+  let Some(default) = options.first().cloned() else {
+    return Ok(Choice::Accept(response.message.clone()));
+  };
+
+  let term = Term::stderr();
+  if !response.reasoning.trim().is_empty() {
+    term.write_line(&format!("{}", style(response.reasoning.trim()).dim()))?;
+  }
+
+  let width = usize::from(term.size().1).saturating_sub(6).max(20);
+  let mut selected = 0;
+  loop {
+    term.write_line(&format!(
+      "{}",
+      style("↑/↓ or 1-9 select · Enter accept · e edit · r regenerate · q keep first").dim()
+    ))?;
+    for (i, option) in options.iter().enumerate() {
+      let marker = if i == selected {
+        ">"
+      } else {
+        " "
+      };
+      let line = format!("{marker} {}. {}", i + 1, fit(option.lines().next().unwrap_or_default(), width));
+      if i == selected {
+        term.write_line(&format!("{}", style(line).cyan().bold()))?;
+      } else {
+        term.write_line(&line)?;
+      }
+    }
+
+    let key = term.read_key()?;
+    term.clear_last_lines(options.len() + 1)?;
+
+    match key {
+      Key::ArrowUp | Key::Char('k') => selected = selected.saturating_sub(1),
+      Key::ArrowDown | Key::Char('j') => selected = (selected + 1).min(options.len() - 1),
+      Key::Char(c @ '1'..='9') => {
+        let index = c as usize - '1' as usize;
+        if index < options.len() {
+          return Ok(Choice::Accept(options[index].clone()));
+        }
+      }
+      Key::Enter => return Ok(Choice::Accept(options[selected].clone())),
+      Key::Char('e') => {
+        let subject = options[selected].lines().next().unwrap_or_default();
+        if let Some(edited) = edit_line(&term, subject)? {
+          if !edited.trim().is_empty() {
+            return Ok(Choice::Accept(with_subject(&options[selected], &edited)));
+          }
+        }
+      }
+      Key::Char('r') => return Ok(Choice::Regenerate),
+      Key::Escape | Key::Char('q') => return Ok(Choice::Accept(default)),
+      _ => {}
+    }
+  }
+}
+
+/// A single-line editor prefilled with `initial`. Returns `None` when cancelled with `Esc`.
+fn edit_line(term: &Term, initial: &str) -> Result<Option<String>> {
+  let mut text: Vec<char> = initial.chars().collect();
+  let mut cursor = text.len();
+
+  loop {
+    term.clear_line()?;
+    term.write_str(&format!("{} {}", style("edit:").dim(), text.iter().collect::<String>()))?;
+    term.move_cursor_left(text.len() - cursor)?;
+
+    match term.read_key()? {
+      Key::Enter => {
+        term.clear_line()?;
+        return Ok(Some(text.into_iter().collect()));
+      }
+      Key::Escape => {
+        term.clear_line()?;
+        return Ok(None);
+      }
+      Key::Backspace if cursor > 0 => {
+        cursor -= 1;
+        text.remove(cursor);
+      }
+      Key::Del if cursor < text.len() => {
+        text.remove(cursor);
+      }
+      Key::ArrowLeft => cursor = cursor.saturating_sub(1),
+      Key::ArrowRight => cursor = (cursor + 1).min(text.len()),
+      Key::Home => cursor = 0,
+      Key::End => cursor = text.len(),
+      Key::Char(c) if !c.is_control() => {
+        text.insert(cursor, c);
+        cursor += 1;
+      }
+      _ => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use super::*;
+  use crate::generation::Strategy;
+
+  #[test]
+  fn test_options_put_selected_message_first_without_duplicates() {
+    let response = CommitResponse {
+      message:    "Add config validation".to_string(),
+      reasoning:  String::new(),
+      files:      HashMap::new(),
+      candidates: vec![
+        "Validate config values".to_string(),
+        "Add config validation".to_string(),
+        "  ".to_string(),
+        "Reject invalid timeouts".to_string(),
+      ],
+      strategy:   Strategy::Local
+    };
+
+    assert_eq!(options(&response), vec![
+      "Add config validation", "Validate config values", "Reject invalid timeouts"
+    ]);
+  }
+
+  #[test]
+  fn test_pick_accepts_selected_message_without_candidates() {
+    let response = CommitResponse {
+      message:    String::new(),
+      reasoning:  String::new(),
+      files:      HashMap::new(),
+      candidates: vec![" ".to_string()],
+      strategy:   Strategy::Local
+    };
+
+    assert_eq!(pick(&response).unwrap(), Choice::Accept(String::new()));
+  }
+
+  #[test]
+  fn test_with_subject_keeps_body() {
+    assert_eq!(with_subject("Old subject\n\nBody text", " New subject "), "New subject\n\nBody text");
+    assert_eq!(with_subject("Old subject", "New subject"), "New subject");
+    assert_eq!(fit("abcdef", 4), "abc…");
+  }
+}
@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use git2::{Commit, Repository, RevparseMode, Sort};
+
+use crate::config::{self, AppConfig};
+use crate::commit;
+use crate::hook::{PatchDiff, PatchRepository, Utf8String};
+use crate::model::Model;
+use crate::multi_step_analysis::FileWithScore;
+use crate::generation::Strategy;
+use crate::multi_step_integration::{generate_commit_response_local, score_files_local};
+
+/// A generated pull request title and markdown description
+#[derive(Debug, Clone)]
+pub struct PullRequest {
+  pub title: String,
+  pub body:  String
+}
+
+/// Prints the title on the first line, followed by a blank line and the body
+impl fmt::Display for PullRequest {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "{}", self.title)?;
+    writeln!(f)?;
+    write!(f, "{}", self.body)
+  }
+}
+
+/// Resolves `base..head` (or `base...head`, or just `base` meaning `base..HEAD`) to
+/// the commit the branch started from and its tip.
+pub fn resolve_range<'a>(repo: &'a Repository, range: &str) -> Result<(Commit<'a>, Commit<'a>)> {
+  let spec = repo
+    .revparse(range)
+    .with_context(|| format!("Failed to resolve revision range '{range}'"))?;
+
+  let base = spec
+    .from()
+    .context("The range has no base")?
+    .peel_to_commit()?;
+  let head = match spec.to() {
+    Some(to) if spec.mode().contains(RevparseMode::RANGE) => to.peel_to_commit()?,
+    _ => repo.head()?.peel_to_commit()?
+  };
+
+  Ok((base, head))
+}
+
+/// The non-merge commits in `base..head`, oldest first
+pub fn range_commits<'a>(repo: &'a Repository, base: &Commit<'_>, head: &Commit<'_>) -> Result<Vec<Commit<'a>>> {
+  let mut revwalk = repo.revwalk()?;
+  revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+  revwalk.push(head.id())?;
+  revwalk.hide(base.id())?;
+
+  let mut commits = Vec::new();
+  for oid in revwalk {
+    let commit = repo.find_commit(oid?)?;
+    if commit.parent_count() <= 1 {
+      commits.push(commit);
+    }
+  }
+
+  Ok(commits)
+}
+
+/// Generates a pull request for `range`: the title comes from the regular commit
+/// message pipeline run over the combined diff, the body from the locally scored files.
+pub async fn generate(repo: &Repository, range: &str, model: Model, settings: Option<&AppConfig>) -> Result<PullRequest> {
+  let (base, head) = resolve_range(repo, range)?;
+  let commits = range_commits(repo, &base, &head)?;
+  if commits.is_empty() {
+    bail!("No commits in range '{range}'");
+  }
+
+  // Diff from where the branch forked, so changes that landed on the base since then
+  // are not attributed to this branch
+  let merge_base = repo.find_commit(repo.merge_base(base.id(), head.id())?)?;
+  let diff = repo.to_tree_diff(Some(merge_base.tree()?), &head.tree()?)?;
+  if diff.is_empty()? {
+    bail!("Range '{range}' has no changes");
+  }
+
+  // Score every file from the untruncated diff; only the generation prompt has to fit
+  // the token budget
+  let full_patch = diff.to_full_patch()?;
+  let files = score_files_local(&full_patch)?;
+
+  let remaining_tokens = commit::remaining_tokens(&model)?;
+  let patch = diff.to_patch(remaining_tokens, model.clone())?;
+  let mut response = commit::generate_response(patch, remaining_tokens, model, settings).await?;
+  if response.strategy == Strategy::Local {
+    // The local analysis costs nothing, so let it see the whole range as well
+    let max_length = settings
+      .and_then(|s| s.max_commit_length)
+      .or(config::APP_CONFIG.max_commit_length);
+    let language = settings
+      .and_then(|s| s.language.as_deref())
+      .or(config::APP_CONFIG.language.as_deref())
+      .unwrap_or(config::DEFAULT_LANGUAGE);
+    response = generate_commit_response_local(&full_patch, max_length, language)?;
+  }
+
+  // Prefer the model's per-file summaries when the API pipeline produced them
+  let files: Vec<FileWithScore> = files
+    .into_iter()
+    .map(|mut file| {
+      if let Some(change) = response.files.get(&file.file_path) {
+        if !change.summary.is_empty() {
+          file.summary = change.summary.clone();
+        }
+      }
+      file
+    })
+    .collect();
+
+  let subjects: Vec<String> = commits
+    .iter()
+    .map(|commit| format!("{} ({:.7})", commit.summary_bytes().unwrap_or_default().to_utf8(), commit.id()))
+    .collect();
+
+  Ok(PullRequest {
+    title: response
+      .message
+      .lines()
+      .next()
+      .unwrap_or_default()
+      .trim()
+      .to_string(),
+    body:  render_body(&files, &subjects)
+  })
+}
+
+/// The directory a file belongs to, used to group notable changes
+fn component(file_path: &str) -> String {
+  match Path::new(file_path).parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => parent.display().to_string(),
+    _ => "(root)".to_string()
+  }
+}
+
+/// `count` followed by `noun`, with an `s` unless the count is one
+fn plural(count: usize, noun: &str) -> String {
+  match count {
+    1 => format!("1 {noun}"),
+    _ => format!("{count} {noun}s")
+  }
+}
+
+/// The summaries of the most impactful files, one sentence each
+fn summarize(files: &[FileWithScore]) -> String {
+  const SUMMARIZED_FILES: usize = 3;
+
+  let mut files: Vec<&FileWithScore> = files
+    .iter()
+    .filter(|f| !f.summary.trim().is_empty())
+    .collect();
+  files.sort_by(|a, b| {
+    b.impact_score
+      .partial_cmp(&a.impact_score)
+      .unwrap_or(std::cmp::Ordering::Equal)
+  });
+  files
+    .iter()
+    .take(SUMMARIZED_FILES)
+    .map(|f| format!("{}.", f.summary.trim().trim_end_matches('.')))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Renders the markdown description: a summary built from the most impactful files,
+/// notable changes grouped by component (most impactful component first), the commits
+/// and testing notes.
+pub fn render_body(files: &[FileWithScore], commits: &[String]) -> String {
+  let mut body = String::new();
+
+  body.push_str("## Summary\n\n");
+  let total_added: u32 = files.iter().map(|f| f.lines_added).sum();
+  let total_removed: u32 = files.iter().map(|f| f.lines_removed).sum();
+  let summary = summarize(files);
+  if !summary.is_empty() {
+    body.push_str(&summary);
+    body.push_str("\n\n");
+  }
+  body.push_str(&format!(
+    "{}, {} changed (+{} -{}).\n\n",
+    plural(commits.len(), "commit"),
+    plural(files.len(), "file"),
+    total_added,
+    total_removed
+  ));
+
+  let mut components: BTreeMap<String, Vec<&FileWithScore>> = BTreeMap::new();
+  for file in files {
+    components
+      .entry(component(&file.file_path))
+      .or_default()
+      .push(file);
+  }
+  let mut components: Vec<(String, Vec<&FileWithScore>)> = components.into_iter().collect();
+  let max_score = |files: &[&FileWithScore]| files.iter().map(|f| f.impact_score).fold(0.0_f32, f32::max);
+  components.sort_by(|(a_name, a), (b_name, b)| {
+    max_score(b)
+      .partial_cmp(&max_score(a))
+      .unwrap_or(std::cmp::Ordering::Equal)
+      .then_with(|| a_name.cmp(b_name))
+  });
+
+  body.push_str("## Notable changes\n");
+  for (name, files) in &components {
+    body.push_str(&format!("\n### `{name}`\n\n"));
+    for file in files {
+      body.push_str(&format!(
+        "- `{}` ({}, +{} -{}): {}\n",
+        file.file_path, file.operation_type, file.lines_added, file.lines_removed, file.summary
+      ));
+    }
+  }
+
+  body.push_str("\n## Commits\n\n");
+  for subject in commits {
+    body.push_str(&format!("- {subject}\n"));
+  }
+
+  body.push_str("\n## Testing\n\n");
+  for note in testing_notes(files) {
+    body.push_str(&format!("- {note}\n"));
+  }
+
+  body
+}
+
+/// Heuristic testing notes derived from the categories of the changed files
+fn testing_notes(files: &[FileWithScore]) -> Vec<String> {
+  let paths_in = |category: &str| -> Vec<String> {
+    files
+      .iter()
+      .filter(|f| f.file_category == category)
+      .map(|f| format!("`{}`", f.file_path))
+      .collect()
+  };
+
+  let mut notes = Vec::new();
+  let tests = paths_in("test");
+  let has_source = files.iter().any(|f| f.file_category == "source");
+  if !tests.is_empty() {
+    notes.push(format!("Tests added or updated: {}", tests.join(", ")));
+  } else if has_source {
+    notes.push("No tests were changed; the source changes above may need coverage".to_string());
+  }
+
+  let build = paths_in("build");
+  if !build.is_empty() {
+    notes.push(format!(
+      "Dependencies or build files changed ({}); verify a clean build",
+      build.join(", ")
+    ));
+  }
+
+  let config = paths_in("config");
+  if !config.is_empty() {
+    notes.push(format!("Configuration changed ({}); check affected environments", config.join(", ")));
+  }
+
+  if notes.is_empty() {
+    notes.push("Documentation or asset changes only; no runtime behavior affected".to_string());
+  }
+
+  notes
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn scored(path: &str, category: &str, score: f32) -> FileWithScore {
+    FileWithScore {
+      file_path:      path.to_string(),
+      operation_type: "modified".to_string(),
+      lines_added:    3,
+      lines_removed:  1,
+      file_category:  category.to_string(),
+      summary:        format!("Changed {path}"),
+      impact_score:   score
+    }
+  }
+
+  #[test]
+  fn test_render_body_groups_by_component_by_impact() {
+    let files = vec![scored("src/config.rs", "source", 0.9), scored("README.md", "docs", 0.2), scored("tests/config_test.rs", "test", 0.5)];
+    let body = render_body(&files, &["Validate config (abc1234)".to_string()]);
+
+    assert!(body.starts_with(
+      "## Summary\n\nChanged src/config.rs. Changed tests/config_test.rs. Changed README.md.\n\n1 commit, 3 files changed (+9 -3)."
+    ));
+    let src = body.find("### `src`").unwrap();
+    let tests = body.find("### `tests`").unwrap();
+    let root = body.find("### `(root)`").unwrap();
+    assert!(src < tests && tests < root, "{body}");
+    assert!(body.contains("- `src/config.rs` (modified, +3 -1): Changed src/config.rs\n"));
+    assert!(body.contains("## Commits\n\n- Validate config (abc1234)\n"));
+    assert!(body.contains("Tests added or updated: `tests/config_test.rs`"));
+  }
+
+  #[test]
+  fn test_render_body_pluralizes_counts() {
+    let one = render_body(&[scored("src/lib.rs", "source", 0.8)], &["Add lib (abc1234)".to_string()]);
+    assert!(one.contains("1 commit, 1 file changed (+3 -1)."), "{one}");
+
+    let commits = vec!["Add lib (abc1234)".to_string(), "Fix lib (def5678)".to_string()];
+    let two = render_body(&[scored("src/lib.rs", "source", 0.8), scored("src/main.rs", "source", 0.4)], &commits);
+    assert!(two.contains("2 commits, 2 files changed (+6 -2)."), "{two}");
+  }
+
+  #[test]
+  fn test_testing_notes_flag_missing_tests_and_build_changes() {
+    let notes = testing_notes(&[scored("src/lib.rs", "source", 0.8), scored("Cargo.toml", "build", 0.4)]);
+    assert!(notes[0].starts_with("No tests were changed"));
+    assert!(notes[1].contains("`Cargo.toml`"));
+  }
+}
@@ -0,0 +1,103 @@
+use anyhow::{bail, Result};
+use git2::Repository;
+
+use crate::{commit, openai};
+use crate::config::AppConfig;
+use crate::hook::{PatchDiff, PatchRepository};
+use crate::model::Model;
+use crate::multi_step_analysis::{ReviewFinding, Severity};
+use crate::multi_step_integration::{review_local, review_with_api};
+
+/// Reviews the staged changes. The local checks always run over the whole diff, so a
+/// secret is caught even when the API is unavailable or the prompt had to be truncated;
+/// the API review adds what those checks cannot see.
+pub async fn review(repo: &Repository, model: Model, settings: &AppConfig) -> Result<Vec<ReviewFinding>> {
+  let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+  let diff = repo.to_commit_diff(head)?;
+  if diff.is_empty()? {
+    bail!("No staged changes to review");
+  }
+
+  let local = review_local(&diff.to_full_patch()?)?;
+
+  let api = match openai::client_for(settings) {
+    Ok(client) => {
+      let remaining_tokens = commit::remaining_tokens(&model)?;
+      let patch = diff.to_patch(remaining_tokens, model.clone())?;
+      match review_with_api(&client, model.as_ref(), &patch).await {
+        Ok(findings) => findings,
+        Err(e) => {
+          log::warn!("Review through the API failed, using the local checks only: {e}");
+          Vec::new()
+        }
+      }
+    }
+    Err(e) => {
+      log::warn!("No usable API configuration, using the local checks only: {e}");
+      Vec::new()
+    }
+  };
+
+  Ok(merge(api, local))
+}
+
+/// Combines API and local findings, dropping local ones the API already reported on
+/// the same line, and orders them by severity (most severe first), path and line.
+pub fn merge(api: Vec<ReviewFinding>, local: Vec<ReviewFinding>) -> Vec<ReviewFinding> {
+  let mut findings = api;
+  for finding in local {
+    let covered = findings.iter().any(|other| {
+      other.file_path == finding.file_path
+        && other.severity >= finding.severity
+        && (other.start_line..=other.end_line).contains(&finding.start_line)
+    });
+    if !covered {
+      findings.push(finding);
+    }
+  }
+
+  findings.sort_by(|a, b| {
+    b.severity
+      .cmp(&a.severity)
+      .then_with(|| a.file_path.cmp(&b.file_path))
+      .then(a.start_line.cmp(&b.start_line))
+  });
+  findings
+}
+
+/// Whether any finding is severe enough to block a commit in hook mode
+pub fn blocks_commit(findings: &[ReviewFinding]) -> bool {
+  findings
+    .iter()
+    .any(|finding| finding.severity == Severity::High)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn finding(path: &str, start_line: u32, end_line: u32, severity: Severity) -> ReviewFinding {
+    ReviewFinding {
+      file_path: path.to_string(),
+      start_line,
+      end_line,
+      severity,
+      message: format!("{severity} in {path}")
+    }
+  }
+
+  #[test]
+  fn test_merge_drops_covered_local_findings_and_sorts() {
+    let api = vec![finding("src/b.rs", 1, 1, Severity::Low), finding("src/a.rs", 10, 14, Severity::High)];
+    let local = vec![finding("src/a.rs", 12, 12, Severity::High), finding("src/a.rs", 3, 3, Severity::Medium)];
+
+    let merged = merge(api, local);
+    let order: Vec<(&str, u32)> = merged
+      .iter()
+      .map(|f| (f.file_path.as_str(), f.start_line))
+      .collect();
+    assert_eq!(order, vec![("src/a.rs", 10), ("src/a.rs", 3), ("src/b.rs", 1)]);
+    assert!(blocks_commit(&merged));
+    assert!(!blocks_commit(&merged[1..]));
+  }
+}
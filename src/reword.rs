@@ -0,0 +1,180 @@
+use anyhow::{bail, Context, Result};
+use git2::{Commit, Oid, Repository, RevparseMode, Sort};
+
+use crate::commit;
+use crate::explain::commit_patch;
+use crate::hook::Utf8String;
+use crate::model::Model;
+
+/// Prefix under which the pre-rewrite tip is kept, mirroring `git filter-branch`
+pub const BACKUP_REF_PREFIX: &str = "refs/original/";
+
+/// A commit together with the message it will be rewritten to
+#[derive(Debug, Clone)]
+pub struct Rewording {
+  pub id:          Oid,
+  pub old_message: String,
+  pub new_message: String
+}
+
+impl Rewording {
+  pub fn old_subject(&self) -> &str {
+    self.old_message.lines().next().unwrap_or_default()
+  }
+
+  pub fn new_subject(&self) -> &str {
+    self.new_message.lines().next().unwrap_or_default()
+  }
+}
+
+/// Resolves `range` to the commits it covers, oldest first.
+///
+/// Accepts `<base>..HEAD` or just `<base>` (meaning `<base>..HEAD`). The range has to
+/// end at `HEAD` and be free of merges, since the rewritten commits are replayed on top
+/// of each other and the current branch is moved to the new tip.
+pub fn commits_in_range<'a>(repo: &'a Repository, range: &str) -> Result<Vec<Commit<'a>>> {
+  let spec = repo
+    .revparse(range)
+    .with_context(|| format!("Failed to resolve revision range '{range}'"))?;
+
+  if spec.mode().contains(RevparseMode::MERGE_BASE) {
+    bail!("Symmetric ranges (A...B) are not supported, use A..HEAD");
+  }
+
+  let head = repo
+    .head()?
+    .peel_to_commit()
+    .context("HEAD does not point to a commit")?;
+  if let Some(to) = spec.to() {
+    if to.peel_to_commit()?.id() != head.id() {
+      bail!("The range must end at HEAD; check out the branch you want to reword first");
+    }
+  }
+
+  let from = spec
+    .from()
+    .context("The range has no start")?
+    .peel_to_commit()?;
+
+  let mut revwalk = repo.revwalk()?;
+  revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+  revwalk.push(head.id())?;
+  revwalk.hide(from.id())?;
+
+  let mut commits = Vec::new();
+  for oid in revwalk {
+    let commit = repo.find_commit(oid?)?;
+    if commit.parent_count() > 1 {
+      bail!("Commit {} is a merge; rewording merges is not supported", commit.id());
+    }
+    commits.push(commit);
+  }
+
+  if commits.is_empty() {
+    bail!("No commits in range '{range}'");
+  }
+
+  Ok(commits)
+}
+
+/// Returns the commits reachable from a remote-tracking branch, i.e. the ones that
+/// have already been pushed somewhere.
+pub fn pushed_commits(repo: &Repository, commits: &[Commit<'_>]) -> Result<Vec<Oid>> {
+  let mut remote_tips = Vec::new();
+  for reference in repo.references_glob("refs/remotes/*")? {
+    if let Some(oid) = reference?.resolve().ok().and_then(|r| r.target()) {
+      remote_tips.push(oid);
+    }
+  }
+
+  let mut pushed = Vec::new();
+  for commit in commits {
+    for tip in &remote_tips {
+      if *tip == commit.id() || repo.graph_descendant_of(*tip, commit.id())? {
+        pushed.push(commit.id());
+        break;
+      }
+    }
+  }
+
+  Ok(pushed)
+}
+
+/// Generates a new message for every commit from its own diff. Commits without
+/// changes (e.g. empty commits) keep their message.
+pub async fn generate_rewordings(repo: &Repository, commits: &[Commit<'_>], model: Model) -> Result<Vec<Rewording>> {
+  let remaining_tokens = commit::remaining_tokens(&model)?;
+  let mut rewordings = Vec::with_capacity(commits.len());
+
+  for commit in commits {
+    let old_message = commit.message_bytes().to_utf8();
+    let patch = commit_patch(repo, commit, remaining_tokens, model.clone())?;
+
+    let new_message = if patch.trim().is_empty() {
+      old_message.clone()
+    } else {
+      commit::generate(patch, remaining_tokens, model.clone(), None)
+        .await?
+        .response
+        .trim()
+        .to_string()
+    };
+
+    rewordings.push(Rewording { id: commit.id(), old_message, new_message });
+  }
+
+  Ok(rewordings)
+}
+
+/// Replays the commits with their new messages on top of the first one's parent,
+/// keeping trees, authors and committers, then moves `HEAD` to the new tip.
+///
+/// The previous tip is saved under [`BACKUP_REF_PREFIX`] first so the rewrite can be
+/// undone with `git reset --hard refs/original/<branch>`.
+pub fn rewrite(repo: &Repository, rewordings: &[Rewording]) -> Result<Oid> {
+  let head = repo.head()?;
+  let old_tip = head.target().context("HEAD does not point to a commit")?;
+  let head_name = if head.is_branch() {
+    head
+      .name()
+      .context("Branch name is not valid UTF-8")?
+      .to_string()
+  } else {
+    "HEAD".to_string()
+  };
+
+  let backup_ref = format!("{BACKUP_REF_PREFIX}{head_name}");
+  repo
+    .reference(&backup_ref, old_tip, true, "git-ai reword: backup")
+    .with_context(|| format!("Failed to create backup ref {backup_ref}"))?;
+
+  let mut parent: Option<Oid> = None;
+  for rewording in rewordings {
+    let original = repo.find_commit(rewording.id)?;
+    let parent_commit = match parent {
+      Some(oid) => Some(repo.find_commit(oid)?),
+      None => original.parent(0).ok()
+    };
+    let parents: Vec<&Commit> = parent_commit.iter().collect();
+
+    parent = Some(repo.commit(
+      None,
+      &original.author(),
+      &original.committer(),
+      &rewording.new_message,
+      &original.tree()?,
+      &parents
+    )?);
+  }
+
+  let new_tip = parent.context("Nothing to rewrite")?;
+  if head.is_branch() {
+    repo
+      .find_reference(&head_name)?
+      .set_target(new_tip, "git-ai reword")?;
+  } else {
+    repo.set_head_detached(new_tip)?;
+  }
+
+  Ok(new_tip)
+}
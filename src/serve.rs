@@ -0,0 +1,327 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use git2::Repository;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::config::{self, AppConfig};
+use crate::generation::CommitResponse;
+use crate::hook::{PatchDiff, PatchRepository};
+use crate::model::Model;
+use crate::{commit, explain, openai, picker, usage};
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Generation, git or configuration failures
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+  jsonrpc: Option<String>,
+  /// Absent for notifications, which get no response
+  id:      Option<Value>,
+  method:  String,
+  #[serde(default)]
+  params:  Value
+}
+
+/// Parameters of `generate` and `candidates`
+#[derive(Debug, Deserialize)]
+struct GenerateParams {
+  /// Any path inside the repository
+  repo:       PathBuf,
+  model:      Option<String>,
+  max_length: Option<usize>
+}
+
+/// Parameters of `explain`
+#[derive(Debug, Deserialize)]
+struct ExplainParams {
+  repo:  PathBuf,
+  #[serde(default = "default_rev")]
+  rev:   String,
+  model: Option<String>
+}
+
+fn default_rev() -> String {
+  "HEAD".to_string()
+}
+
+/// An error as sent in a JSON-RPC response
+#[derive(Debug)]
+struct RpcError {
+  code:    i64,
+  message: String
+}
+
+impl RpcError {
+  fn new(code: i64, message: impl Into<String>) -> Self {
+    RpcError { code, message: message.into() }
+  }
+}
+
+impl From<anyhow::Error> for RpcError {
+  fn from(e: anyhow::Error) -> Self {
+    RpcError::new(SERVER_ERROR, format!("{e:#}"))
+  }
+}
+
+/// The write half of the connection: one JSON message per line
+struct Connection<W> {
+  output: W
+}
+
+impl<W: AsyncWrite + Unpin> Connection<W> {
+  async fn send(&mut self, message: Value) -> Result<()> {
+    let mut line = serde_json::to_vec(&message)?;
+    line.push(b'\n');
+    self.output.write_all(&line).await?;
+    self.output.flush().await?;
+    Ok(())
+  }
+
+  /// Sends a `progress` notification for the request `id`
+  async fn progress(&mut self, id: &Value, stage: &str, message: &str) -> Result<()> {
+    self
+      .send(json!({
+        "jsonrpc": "2.0",
+        "method": "progress",
+        "params": { "id": id, "stage": stage, "message": message }
+      }))
+      .await
+  }
+
+  async fn respond(&mut self, id: Value, result: Result<Value, RpcError>) -> Result<()> {
+    let message = match result {
+      Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+      Err(error) =>
+        json!({
+          "jsonrpc": "2.0",
+          "id": id,
+          "error": { "code": error.code, "message": error.message }
+        }),
+    };
+    self.send(message).await
+  }
+}
+
+fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+  serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, format!("Invalid params: {e}")))
+}
+
+fn model_or_default(model: Option<String>, settings: &AppConfig) -> Model {
+  model
+    .or_else(|| settings.model.clone())
+    .unwrap_or(config::DEFAULT_MODEL.to_string())
+    .into()
+}
+
+/// The configuration for a request on `repo`. It is resolved per request, so the `ai.*`
+/// keys of that repository and the current profile apply rather than those of the
+/// directory the server was started in.
+fn settings_for(repo: &Repository) -> Result<AppConfig> {
+  Ok(openai::with_env_api_key(AppConfig::for_repo(repo)?))
+}
+
+fn open(path: &PathBuf) -> Result<Repository> {
+  let repo = Repository::discover(path).with_context(|| format!("Failed to open repository at {}", path.display()))?;
+  if let Some(workdir) = repo.workdir() {
+    usage::set_repo(workdir);
+  }
+  Ok(repo)
+}
+
+/// Runs the same pipeline as `git ai generate` on the staged changes of `params.repo`
+async fn generate<W: AsyncWrite + Unpin>(conn: &mut Connection<W>, id: &Value, params: GenerateParams) -> Result<CommitResponse> {
+  conn
+    .progress(id, "diff", "Reading the staged changes")
+    .await?;
+  let repo = open(&params.repo)?;
+  let mut settings = settings_for(&repo)?;
+  if let Some(max_length) = params.max_length {
+    settings.max_commit_length = Some(max_length);
+  }
+  let model = model_or_default(params.model, &settings);
+  let remaining_tokens = commit::remaining_tokens_for(&model, &settings)?;
+
+  let tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+  if repo.to_commit_diff(tree.clone())?.is_empty()? {
+    bail!("No staged changes to generate a commit message for");
+  }
+  let patch = repo
+    .to_patch(tree, remaining_tokens, model.clone())
+    .context("Failed to get patch")?;
+  drop(repo);
+
+  conn
+    .progress(id, "generate", &format!("Generating with {model}"))
+    .await?;
+  commit::generate_response(patch, remaining_tokens, model, Some(&settings)).await
+}
+
+async fn explain_commit<W: AsyncWrite + Unpin>(conn: &mut Connection<W>, id: &Value, params: ExplainParams) -> Result<Value> {
+  conn
+    .progress(id, "explain", &format!("Explaining {}", params.rev))
+    .await?;
+  let repo = open(&params.repo)?;
+  let settings = settings_for(&repo)?;
+  let model = model_or_default(params.model, &settings);
+  let explanation = explain::explain(&repo, &params.rev, model, Some(&settings)).await?;
+  Ok(serde_json::to_value(explanation)?)
+}
+
+async fn dispatch<W: AsyncWrite + Unpin>(conn: &mut Connection<W>, id: &Value, method: &str, raw: Value) -> Result<Value, RpcError> {
+  match method {
+    "generate" => {
+      let response = generate(conn, id, params(raw)?).await?;
+      Ok(serde_json::to_value(response).map_err(anyhow::Error::from)?)
+    }
+    "candidates" => {
+      let response = generate(conn, id, params(raw)?).await?;
+      Ok(json!({ "candidates": picker::options(&response) }))
+    }
+    "explain" => Ok(explain_commit(conn, id, params(raw)?).await?),
+    _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{method}'")))
+  }
+}
+
+/// Serves JSON-RPC 2.0 requests, one JSON object per line, until `shutdown` or the end of
+/// `input`. Requests are handled one at a time; while one runs, `progress` notifications
+/// carrying its id report each stage.
+///
+/// Methods: `generate` and `candidates` (`repo`, optional `model` and `max_length`),
+/// `explain` (`repo`, optional `rev` and `model`) and `shutdown`.
+pub async fn serve<R, W>(input: R, output: W) -> Result<()>
+where
+  R: AsyncBufRead + Unpin,
+  W: AsyncWrite + Unpin
+{
+  let mut conn = Connection { output };
+  let mut lines = input.lines();
+
+  while let Some(line) = lines.next_line().await? {
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let request: Request = match serde_json::from_str::<Value>(&line) {
+      Err(e) => {
+        conn
+          .respond(Value::Null, Err(RpcError::new(PARSE_ERROR, format!("Parse error: {e}"))))
+          .await?;
+        continue;
+      }
+      Ok(value) => {
+        let id = value.get("id").cloned().unwrap_or(Value::Null);
+        match serde_json::from_value::<Request>(value) {
+          Ok(request) if request.jsonrpc.as_deref() == Some("2.0") => request,
+          Ok(_) => {
+            conn
+              .respond(id, Err(RpcError::new(INVALID_REQUEST, "Invalid request: jsonrpc must be \"2.0\"")))
+              .await?;
+            continue;
+          }
+          Err(e) => {
+            conn
+              .respond(id, Err(RpcError::new(INVALID_REQUEST, format!("Invalid request: {e}"))))
+              .await?;
+            continue;
+          }
+        }
+      }
+    };
+
+    if request.method == "shutdown" {
+      if let Some(id) = request.id {
+        conn.respond(id, Ok(Value::Null)).await?;
+      }
+      break;
+    }
+
+    let id = request.id.clone().unwrap_or(Value::Null);
+    let result = dispatch(&mut conn, &id, &request.method, request.params).await;
+    if let Err(error) = &result {
+      log::debug!("{} failed: {}", request.method, error.message);
+    }
+    if let Some(id) = request.id {
+      conn.respond(id, result).await?;
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  async fn exchange(input: &str) -> Vec<Value> {
+    let mut output = Vec::new();
+    serve(input.as_bytes(), &mut output).await.unwrap();
+    String::from_utf8(output)
+      .unwrap()
+      .lines()
+      .map(|line| serde_json::from_str(line).unwrap())
+      .collect()
+  }
+
+  #[tokio::test]
+  async fn test_protocol_errors_and_shutdown() {
+    let responses = exchange(concat!(
+      "not json\n",
+      "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"rebase\"}\n",
+      "{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"generate\",\"params\":{}}\n",
+      "{\"jsonrpc\":\"1.0\",\"id\":3,\"method\":\"generate\"}\n",
+      "{\"jsonrpc\":\"2.0\",\"method\":\"rebase\"}\n",
+      "{\"jsonrpc\":\"2.0\",\"id\":4,\"method\":\"shutdown\"}\n",
+      "{\"jsonrpc\":\"2.0\",\"id\":5,\"method\":\"rebase\"}\n"
+    ))
+    .await;
+
+    let codes: Vec<(Value, Value)> = responses
+      .iter()
+      .map(|response| (response["id"].clone(), response["error"]["code"].clone()))
+      .collect();
+    assert_eq!(codes, vec![
+      (Value::Null, json!(PARSE_ERROR)),
+      (json!(1), json!(METHOD_NOT_FOUND)),
+      (json!(2), json!(INVALID_PARAMS)),
+      (json!(3), json!(INVALID_REQUEST)),
+      (json!(4), Value::Null)
+    ]);
+    assert_eq!(responses[4]["result"], Value::Null);
+  }
+
+  #[test]
+  fn test_settings_come_from_the_requested_repository() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let mut git_config = repo.config().unwrap();
+    git_config.set_str("ai.language", "sv").unwrap();
+    git_config.set_str("ai.model", "gpt-4.1-nano").unwrap();
+
+    let settings = settings_for(&repo).unwrap();
+    assert_eq!(settings.language.as_deref(), Some("sv"));
+    assert_eq!(model_or_default(None, &settings).to_string(), "gpt-4.1-nano");
+    assert_eq!(model_or_default(Some("gpt-4.1".to_string()), &settings).to_string(), "gpt-4.1");
+  }
+
+  #[tokio::test]
+  async fn test_generate_reports_progress_before_errors() {
+    let dir = tempfile::TempDir::new().unwrap();
+    Repository::init(dir.path()).unwrap();
+    let request = json!({ "jsonrpc": "2.0", "id": 7, "method": "generate", "params": { "repo": dir.path() } });
+
+    let responses = exchange(&format!("{request}\n")).await;
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["method"], "progress");
+    assert_eq!(responses[0]["params"]["id"], 7);
+    assert_eq!(responses[1]["error"]["code"], SERVER_ERROR);
+    assert_eq!(responses[1]["error"]["message"], "No staged changes to generate a commit message for");
+  }
+}
@@ -6,7 +6,7 @@ use async_openai::types::chat::{
 use async_openai::Client;
 
 use crate::function_calling::{create_commit_function_tool, CommitFunctionArgs};
-use crate::debug_output;
+use crate::{debug_output, usage};
 
 /// Simplified multi-step commit message generation that works with raw diff
 pub async fn generate_commit_message_simple(
@@ -44,6 +44,7 @@ pub async fn generate_commit_message_simple(
     .build()?;
 
   let response = client.chat().create(request).await?;
+  usage::record("simple", model, response.usage.as_ref());
 
   let tool_call_arguments = first_function_call_arguments(&response);
 
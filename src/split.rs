@@ -0,0 +1,455 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use git2::{ApplyOptions, Diff, DiffOptions, Oid, Repository, Tree};
+
+use crate::commit;
+use crate::hook::{DiffDeltaPath, PatchDiff, PatchRepository};
+use crate::model::Model;
+use crate::multi_step_analysis::analyze_file;
+
+/// A single staged hunk. Files without textual hunks (binary files, empty new files,
+/// mode changes) are represented by one hunk covering the whole file.
+#[derive(Debug, Clone)]
+pub struct Hunk {
+  pub path:      PathBuf,
+  /// Position of the hunk within its file, `None` for whole-file changes
+  pub index:     Option<usize>,
+  pub header:    String,
+  pub added:     u32,
+  pub removed:   u32,
+  /// First and last line of the `HEAD` version the hunk touches: the removed lines, or
+  /// for pure additions the lines around the insertion point
+  pub old_lines: Option<(u32, u32)>,
+  /// Whether every changed line is a comment or blank
+  pub comment:   bool
+}
+
+/// A logical change: the hunks that will end up in the same commit
+#[derive(Debug, Clone)]
+pub struct Group {
+  pub name:  String,
+  pub hunks: Vec<Hunk>
+}
+
+impl Group {
+  /// The files this group touches with their line counts, in path order
+  pub fn files(&self) -> Vec<(PathBuf, u32, u32)> {
+    let mut files: BTreeMap<&Path, (u32, u32)> = BTreeMap::new();
+    for hunk in &self.hunks {
+      let entry = files.entry(&hunk.path).or_default();
+      entry.0 += hunk.added;
+      entry.1 += hunk.removed;
+    }
+    files
+      .into_iter()
+      .map(|(path, (added, removed))| (path.to_path_buf(), added, removed))
+      .collect()
+  }
+}
+
+/// One commit of the proposed series
+#[derive(Debug, Clone)]
+pub struct PlannedCommit {
+  pub group:   Group,
+  pub tree:    Oid,
+  pub message: String
+}
+
+/// Diffs `HEAD` against the index with every change preserved, so the hunks can be
+/// applied back exactly; whitespace is not ignored and binary content is included.
+pub fn staged_diff(repo: &Repository) -> Result<(Option<Tree<'_>>, Diff<'_>)> {
+  let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+  let mut opts = DiffOptions::new();
+  opts.show_binary(true).context_lines(3).interhunk_lines(0);
+  let diff = repo
+    .diff_tree_to_index(head.as_ref(), None, Some(&mut opts))
+    .context("Failed to diff the index")?;
+  Ok((head, diff))
+}
+
+/// Splits the per-file patches from [`PatchDiff::collect_diff_data`] into hunks, ordered
+/// by path and position.
+pub fn collect_hunks(diff: &Diff<'_>) -> Result<Vec<Hunk>> {
+  let mut files: Vec<(PathBuf, String)> = diff.collect_diff_data()?.into_iter().collect();
+  files.sort_by(|a, b| a.0.cmp(&b.0));
+
+  let mut hunks = Vec::new();
+  for (path, patch) in files {
+    let comments = comment_prefixes(&path);
+    let mut file_hunks: Vec<Hunk> = Vec::new();
+    // The `HEAD` line number the next context or removed line has
+    let mut old_line = 0;
+    for line in patch.lines() {
+      if line.starts_with("@@") {
+        old_line = old_start(line).unwrap_or(1);
+        file_hunks.push(Hunk {
+          path:      path.clone(),
+          index:     Some(file_hunks.len()),
+          header:    line.to_string(),
+          added:     0,
+          removed:   0,
+          old_lines: None,
+          comment:   true
+        });
+        continue;
+      }
+
+      let Some(hunk) = file_hunks.last_mut() else {
+        continue;
+      };
+      let (added, content) = match line.split_at_checked(1) {
+        Some(("+", content)) => (true, content),
+        Some(("-", content)) => (false, content),
+        Some((" ", _)) => {
+          old_line += 1;
+          continue;
+        }
+        _ => continue
+      };
+      if added {
+        // The lines before and after the insertion point; new files have none
+        if hunk.removed == 0 && hunk.old_lines.is_none() && old_line > 0 {
+          let before = old_line.saturating_sub(1).max(1);
+          hunk.old_lines = Some((before, before + 1));
+        }
+        hunk.added += 1;
+      } else {
+        let first = match hunk.old_lines {
+          Some((first, _)) if hunk.removed > 0 => first,
+          _ => old_line
+        };
+        hunk.old_lines = Some((first, old_line));
+        hunk.removed += 1;
+        old_line += 1;
+      }
+      hunk.comment &= is_comment(content, comments);
+    }
+
+    if file_hunks.is_empty() {
+      file_hunks.push(Hunk {
+        path,
+        index: None,
+        header: String::new(),
+        added: 0,
+        removed: 0,
+        old_lines: None,
+        comment: false
+      });
+    }
+    hunks.extend(file_hunks);
+  }
+
+  Ok(hunks)
+}
+
+/// The first `HEAD` line of a hunk header like `@@ -12,3 +12,4 @@`
+fn old_start(header: &str) -> Option<u32> {
+  let old_range = header
+    .split_whitespace()
+    .find(|part| part.starts_with('-'))?;
+  old_range[1..].split(',').next()?.parse().ok()
+}
+
+/// How line and block comments start in the language of `path`, judged by its extension.
+/// Languages not listed have none, so only their blank lines count as comments.
+fn comment_prefixes(path: &Path) -> &'static [&'static str] {
+  const C_LIKE: &[&str] = &["//", "/*", "* ", "*/"];
+  const HASH: &[&str] = &["#"];
+  const DASHES: &[&str] = &["--"];
+  const MARKUP: &[&str] = &["<!--"];
+
+  let name = path
+    .file_name()
+    .and_then(|name| name.to_str())
+    .unwrap_or_default();
+  if matches!(name, "Makefile" | "Dockerfile" | "Gemfile" | "Rakefile") {
+    return HASH;
+  }
+  match path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .unwrap_or_default()
+  {
+    "rs" | "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "java" | "kt" | "kts" | "scala" | "swift" | "go" | "js" | "jsx" | "mjs" | "cjs"
+    | "ts" | "tsx" | "cs" | "dart" | "php" | "css" | "scss" | "proto" => C_LIKE,
+    "sh" | "bash" | "zsh" | "fish" | "py" | "rb" | "pl" | "r" | "toml" | "yaml" | "yml" | "nix" | "cmake" | "mk" | "conf" => HASH,
+    "sql" | "lua" | "hs" | "elm" => DASHES,
+    "html" | "xml" | "svg" | "vue" | "md" => MARKUP,
+    _ => &[]
+  }
+}
+
+/// Whether `line` is blank or starts with one of the comment `prefixes` of its language
+fn is_comment(line: &str, prefixes: &[&str]) -> bool {
+  let line = line.trim();
+  // A bare `*` continues a block comment in C-like languages
+  line.is_empty() || (line == "*" && prefixes.contains(&"* ")) || prefixes.iter().any(|prefix| line.starts_with(prefix))
+}
+
+/// The source file a test most likely covers, e.g. `tests/config_test.rs` -> `config`
+fn test_subject(path: &Path) -> Option<String> {
+  let stem = path.file_stem()?.to_str()?;
+  let stem = stem.split('.').next().unwrap_or(stem);
+  let subject = stem
+    .strip_suffix("_test")
+    .or_else(|| stem.strip_suffix("_spec"))
+    .or_else(|| stem.strip_prefix("test_"))
+    .unwrap_or(stem);
+  Some(subject.to_string())
+}
+
+/// Clusters hunks into logical changes:
+///
+/// - dependency and build files first, then configuration,
+/// - source files grouped by directory, with the tests that cover them,
+/// - remaining tests,
+/// - documentation last, including comment-only hunks from source files.
+///
+/// Groups keep their hunks in path order so they apply cleanly.
+pub fn cluster(hunks: &[Hunk]) -> Vec<Group> {
+  let category = |hunk: &Hunk| -> String {
+    let path = hunk.path.to_string_lossy();
+    let category = analyze_file(&path, "", "modified").file_category;
+    if category == "source" && path.starts_with("tests/") {
+      "test".to_string()
+    } else {
+      category
+    }
+  };
+
+  // Source directories by file stem, so tests can join the code they cover
+  let mut source_dirs: BTreeMap<String, String> = BTreeMap::new();
+  for hunk in hunks.iter().filter(|hunk| category(hunk) == "source") {
+    if let Some(stem) = hunk.path.file_stem().and_then(|s| s.to_str()) {
+      source_dirs
+        .entry(stem.to_string())
+        .or_insert_with(|| directory(&hunk.path));
+    }
+  }
+
+  let mut groups: BTreeMap<(u8, String), Vec<Hunk>> = BTreeMap::new();
+  for hunk in hunks {
+    let category = category(hunk);
+    let key = match category.as_str() {
+      "build" => (0, "dependencies".to_string()),
+      "config" => (1, "configuration".to_string()),
+      "docs" => (4, "documentation".to_string()),
+      "source" if hunk.comment && hunk.index.is_some() => (4, "documentation".to_string()),
+      "test" =>
+        match test_subject(&hunk.path).and_then(|subject| source_dirs.get(&subject)) {
+          Some(dir) => (2, dir.clone()),
+          None => (3, "tests".to_string())
+        },
+      _ => (2, directory(&hunk.path))
+    };
+    groups.entry(key).or_default().push(hunk.clone());
+  }
+
+  groups
+    .into_iter()
+    .map(|((_, name), mut hunks)| {
+      hunks.sort_by(|a, b| a.path.cmp(&b.path).then(a.index.cmp(&b.index)));
+      Group { name, hunks }
+    })
+    .collect()
+}
+
+fn directory(path: &Path) -> String {
+  match path.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => parent.display().to_string(),
+    _ => "(root)".to_string()
+  }
+}
+
+/// Applies the selected hunks of `diff` to `base` and writes the resulting tree
+fn apply_hunks(repo: &Repository, base: &Tree<'_>, diff: &Diff<'_>, selected: &HashSet<(PathBuf, Option<usize>)>) -> Result<Oid> {
+  let paths: HashSet<&PathBuf> = selected.iter().map(|(path, _)| path).collect();
+  let current: RefCell<(PathBuf, usize)> = RefCell::new((PathBuf::new(), 0));
+
+  let mut opts = ApplyOptions::new();
+  opts.delta_callback(|delta| {
+    let Some(delta) = delta else {
+      return false;
+    };
+    let path = delta.path();
+    let include = paths.contains(&path);
+    *current.borrow_mut() = (path, 0);
+    include
+  });
+  opts.hunk_callback(|_hunk| {
+    let mut current = current.borrow_mut();
+    let index = current.1;
+    current.1 += 1;
+    selected.contains(&(current.0.clone(), Some(index)))
+  });
+
+  let mut index = repo
+    .apply_to_tree(base, diff, Some(&mut opts))
+    .context("Failed to apply hunks")?;
+  Ok(index.write_tree_to(repo)?)
+}
+
+/// Builds the tree after each group by applying the hunks of that group and every
+/// group before it to `HEAD`. Fails if the last tree differs from the index, so a split
+/// never changes what ends up committed.
+pub fn build_trees(repo: &Repository, base: Option<&Tree<'_>>, diff: &Diff<'_>, groups: &[Group]) -> Result<Vec<Oid>> {
+  let empty;
+  let base = match base {
+    Some(base) => base,
+    None => {
+      empty = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+      &empty
+    }
+  };
+
+  let mut selected = HashSet::new();
+  let mut trees = Vec::with_capacity(groups.len());
+  for group in groups {
+    selected.extend(
+      group
+        .hunks
+        .iter()
+        .map(|hunk| (hunk.path.clone(), hunk.index))
+    );
+    trees.push(apply_hunks(repo, base, diff, &selected)?);
+  }
+
+  let staged = repo.index()?.write_tree()?;
+  if trees.last() != Some(&staged) {
+    bail!("The split does not reproduce the staged changes exactly; commit them as one instead");
+  }
+
+  Ok(trees)
+}
+
+/// Proposes a series of commits for the staged changes, each with a generated message
+pub async fn plan(repo: &Repository, model: Model) -> Result<Vec<PlannedCommit>> {
+  let (head, diff) = staged_diff(repo)?;
+  if diff.is_empty()? {
+    bail!("No staged changes to split");
+  }
+
+  let groups = cluster(&collect_hunks(&diff)?);
+  let trees = build_trees(repo, head.as_ref(), &diff, &groups)?;
+  let remaining_tokens = commit::remaining_tokens(&model)?;
+
+  let mut previous = head.clone();
+  let mut planned = Vec::with_capacity(groups.len());
+  for (group, tree_id) in groups.into_iter().zip(trees) {
+    let tree = repo.find_tree(tree_id)?;
+    let patch = repo
+      .to_tree_diff(previous, &tree)?
+      .to_patch(remaining_tokens, model.clone())?;
+    let message = commit::generate(patch, remaining_tokens, model.clone(), None)
+      .await?
+      .response
+      .trim()
+      .to_string();
+
+    planned.push(PlannedCommit { group, tree: tree_id, message });
+    previous = Some(tree);
+  }
+
+  Ok(planned)
+}
+
+/// Commits the planned trees on top of `HEAD`, one after another. The index already
+/// matches the last tree, so nothing is left staged afterwards.
+pub fn apply(repo: &Repository, planned: &[PlannedCommit]) -> Result<Vec<Oid>> {
+  let signature = repo
+    .signature()
+    .context("Failed to determine the committer")?;
+  let mut parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+  let mut commits = Vec::with_capacity(planned.len());
+
+  for commit in planned {
+    let tree = repo.find_tree(commit.tree)?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    let id = repo.commit(Some("HEAD"), &signature, &signature, &commit.message, &tree, &parents)?;
+    commits.push(id);
+    parent = Some(repo.find_commit(id)?);
+  }
+
+  Ok(commits)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hunk(path: &str, index: Option<usize>, comment: bool) -> Hunk {
+    Hunk {
+      path: PathBuf::from(path),
+      index,
+      header: String::new(),
+      added: 1,
+      removed: 0,
+      old_lines: None,
+      comment
+    }
+  }
+
+  #[test]
+  fn test_cluster_orders_and_pairs_tests_with_sources() {
+    let groups = cluster(&[
+      hunk("README.md", Some(0), false),
+      hunk("src/split.rs", Some(0), false),
+      hunk("src/split.rs", Some(1), true),
+      hunk("tests/split_test.rs", Some(0), false),
+      hunk("tests/other_test.rs", Some(0), false),
+      hunk("Cargo.toml", Some(0), false)
+    ]);
+
+    let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+    assert_eq!(names, vec!["dependencies", "src", "tests", "documentation"]);
+    assert_eq!(groups[1].files().len(), 2);
+    assert_eq!(groups[3].files(), vec![
+      (PathBuf::from("README.md"), 1, 0),
+      (PathBuf::from("src/split.rs"), 1, 0)
+    ]);
+  }
+
+  #[test]
+  fn test_is_comment() {
+    let rust = comment_prefixes(Path::new("src/split.rs"));
+    assert!(is_comment("  // explain why", rust));
+    assert!(is_comment("   * continued", rust));
+    assert!(is_comment("", rust));
+    assert!(!is_comment("let x = 1; // trailing", rust));
+    assert!(!is_comment("#[derive(Debug)]", rust));
+    assert!(!is_comment("*ptr = 0;", comment_prefixes(Path::new("src/lib.c"))));
+    assert!(!is_comment("#include <stdio.h>", comment_prefixes(Path::new("src/lib.h"))));
+
+    assert!(is_comment("# explain why", comment_prefixes(Path::new("scripts/release.sh"))));
+    assert!(is_comment("-- explain why", comment_prefixes(Path::new("db/schema.sql"))));
+    assert!(!is_comment("local x = 1", comment_prefixes(Path::new("init.lua"))));
+  }
+
+  #[test]
+  fn test_attribute_hunks_stay_with_the_source() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let source = "struct Config;\n\nfn main() {}\n";
+    std::fs::write(dir.path().join("lib.rs"), source).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("lib.rs")).unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("Dev", "dev@example.com").unwrap();
+    repo
+      .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+      .unwrap();
+
+    std::fs::write(dir.path().join("lib.rs"), format!("#[derive(Debug)]\n{source}")).unwrap();
+    index.add_path(Path::new("lib.rs")).unwrap();
+    index.write().unwrap();
+
+    let (_, diff) = staged_diff(&repo).unwrap();
+    let hunks = collect_hunks(&diff).unwrap();
+    assert_eq!(hunks.len(), 1);
+    assert!(!hunks[0].comment);
+    assert_eq!(cluster(&hunks)[0].name, "(root)");
+  }
+}
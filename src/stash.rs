@@ -0,0 +1,103 @@
+use anyhow::{bail, Context, Result};
+use git2::{Oid, Repository, StashFlags};
+
+use crate::commit;
+use crate::config::AppConfig;
+use crate::explain::commit_patch;
+use crate::hook::{PatchDiff, PatchRepository};
+use crate::model::Model;
+
+/// An entry of the stash list
+#[derive(Debug, Clone)]
+pub struct StashEntry {
+  pub index:   usize,
+  pub message: String,
+  pub id:      Oid
+}
+
+impl StashEntry {
+  /// Whether the stash only has git's default `WIP on <branch>: <sha> <subject>` message
+  pub fn is_unnamed(&self) -> bool {
+    is_unnamed(&self.message)
+  }
+}
+
+fn is_unnamed(message: &str) -> bool {
+  message.starts_with("WIP on ")
+}
+
+fn first_line(message: &str) -> String {
+  message
+    .lines()
+    .next()
+    .unwrap_or_default()
+    .trim()
+    .to_string()
+}
+
+/// Generates a stash message for the uncommitted changes: staged, unstaged and untracked
+pub async fn generate_message(repo: &Repository, model: Model, settings: Option<&AppConfig>) -> Result<String> {
+  let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+  let diff = repo.to_diff(head)?;
+  if diff.is_empty()? {
+    bail!("No local changes to stash");
+  }
+
+  let remaining_tokens = commit::remaining_tokens(&model)?;
+  let patch = diff.to_patch(remaining_tokens, model.clone())?;
+  let response = commit::generate(patch, remaining_tokens, model, settings).await?;
+  Ok(first_line(&response.response))
+}
+
+/// Stashes the index, working tree and untracked files under `message`, the same set of
+/// changes the message was generated from. Git lists it as `On <branch>: <message>`.
+pub fn save(repo: &mut Repository, message: &str) -> Result<Oid> {
+  let signature = repo
+    .signature()
+    .context("Failed to determine the committer")?;
+  repo
+    .stash_save(&signature, message, Some(StashFlags::INCLUDE_UNTRACKED))
+    .context("Failed to create the stash")
+}
+
+/// The stash list, newest first
+pub fn list(repo: &mut Repository) -> Result<Vec<StashEntry>> {
+  let mut entries = Vec::new();
+  repo.stash_foreach(|index, message, id| {
+    entries.push(StashEntry { index, message: message.to_string(), id: *id });
+    true
+  })?;
+  Ok(entries)
+}
+
+/// Summarizes what a stash holds from the diff between its base commit and its working
+/// tree, for stashes created without a message. Stashes of untracked files only keep
+/// their changes in the third parent, so those are summarized from that commit instead.
+pub async fn summarize(repo: &Repository, entry: &StashEntry, model: Model) -> Result<String> {
+  let commit = repo.find_commit(entry.id)?;
+  let remaining_tokens = commit::remaining_tokens(&model)?;
+  let mut patch = commit_patch(repo, &commit, remaining_tokens, model.clone())?;
+  if patch.trim().is_empty() {
+    if let Ok(untracked) = commit.parent(2) {
+      patch = commit_patch(repo, &untracked, remaining_tokens, model.clone())?;
+    }
+  }
+  if patch.trim().is_empty() {
+    bail!("stash@{{{}}} has no changes to summarize", entry.index);
+  }
+
+  let response = commit::generate(patch, remaining_tokens, model, None).await?;
+  Ok(first_line(&response.response))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_is_unnamed() {
+    assert!(is_unnamed("WIP on main: 1a2b3c4 Add config validation"));
+    assert!(!is_unnamed("On main: Validate timeouts"));
+    assert_eq!(first_line("Validate timeouts\n\nBody"), "Validate timeouts");
+  }
+}
@@ -0,0 +1,299 @@
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+use git2::{Commit, Oid, Repository, Sort};
+
+use crate::changelog::{classify, commit_files, entry_for, merge_entries, parse_subject, render_entries, Entry, Section};
+use crate::hook::Utf8String;
+use crate::multi_step_analysis::FileWithScore;
+
+/// A `MAJOR.MINOR.PATCH` version as used in release tags, optionally prefixed with `v`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+  pub major:  u64,
+  pub minor:  u64,
+  pub patch:  u64,
+  /// Whether the tag name starts with `v`, kept for the next tag
+  pub prefix: bool
+}
+
+impl Version {
+  /// Parses tag names like `v1.2.3` or `1.2.3`. Pre-release and build suffixes are not
+  /// accepted, so release candidates never count as the previous release.
+  pub fn parse(name: &str) -> Option<Self> {
+    let prefix = name.starts_with('v');
+    let mut parts = name.trim_start_matches('v').split('.');
+    let mut next = || parts.next()?.parse::<u64>().ok();
+    let version = Version {
+      major: next()?,
+      minor: next()?,
+      patch: next()?,
+      prefix
+    };
+    parts.next().is_none().then_some(version)
+  }
+
+  /// The part of this version `bump` actually changes. Before 1.0.0 breaking changes bump
+  /// the minor version and everything else the patch version, as Cargo does.
+  pub fn applied(&self, bump: Bump) -> Bump {
+    match bump {
+      Bump::Major if self.major == 0 => Bump::Minor,
+      Bump::Minor if self.major == 0 => Bump::Patch,
+      bump => bump
+    }
+  }
+
+  /// The next version for `bump`, see [`Version::applied`]
+  pub fn bump(&self, bump: Bump) -> Self {
+    let (major, minor, patch) = match self.applied(bump) {
+      Bump::Major => (self.major + 1, 0, 0),
+      Bump::Minor => (self.major, self.minor + 1, 0),
+      Bump::Patch => (self.major, self.minor, self.patch + 1)
+    };
+    Version { major, minor, patch, prefix: self.prefix }
+  }
+}
+
+impl fmt::Display for Version {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let prefix = if self.prefix {
+      "v"
+    } else {
+      ""
+    };
+    write!(f, "{prefix}{}.{}.{}", self.major, self.minor, self.patch)
+  }
+}
+
+/// Which part of the version a release bumps, ordered by significance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bump {
+  Patch,
+  Minor,
+  Major
+}
+
+impl fmt::Display for Bump {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      Bump::Patch => "patch",
+      Bump::Minor => "minor",
+      Bump::Major => "major"
+    };
+    write!(f, "{name}")
+  }
+}
+
+/// The bump a single commit calls for: a `!` after the type or a `BREAKING CHANGE:`
+/// footer is breaking, a change the changelog files under Added is a feature, and
+/// anything else a fix.
+pub fn bump_for(message: &str, files: &[FileWithScore]) -> Bump {
+  let subject = message.lines().next().unwrap_or_default();
+  let breaking_footer = message
+    .lines()
+    .any(|line| line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:"));
+
+  if parse_subject(subject).breaking || breaking_footer {
+    Bump::Major
+  } else if classify(subject, files) == Section::Added {
+    Bump::Minor
+  } else {
+    Bump::Patch
+  }
+}
+
+/// A proposed release: the next tag on `HEAD` and its annotated message
+#[derive(Debug, Clone)]
+pub struct Release {
+  /// The previous release tag, `None` for the first release
+  pub previous: Option<String>,
+  /// The part of the previous version this release bumps
+  pub bump:     Bump,
+  pub name:     String,
+  pub target:   Oid,
+  pub commits:  usize,
+  /// The tag message: the tag name followed by changelog-style sections
+  pub message:  String
+}
+
+/// The highest version tag that `HEAD` contains, with the commit it points at
+pub fn previous_release<'a>(repo: &'a Repository, head: &Commit<'_>) -> Result<Option<(String, Version, Commit<'a>)>> {
+  let mut latest: Option<(String, Version, Commit<'a>)> = None;
+
+  let names = repo.tag_names(None)?;
+  // Names that are not valid UTF-8 cannot be versions
+  for name in names.iter().flatten().flatten() {
+    let Some(version) = Version::parse(name) else {
+      continue;
+    };
+    if latest
+      .as_ref()
+      .is_some_and(|(_, latest, _)| *latest >= version)
+    {
+      continue;
+    }
+
+    // Tags of trees or blobs cannot be releases of a commit
+    let Ok(commit) = repo
+      .revparse_single(&format!("refs/tags/{name}"))
+      .and_then(|object| object.peel_to_commit())
+    else {
+      continue;
+    };
+    if commit.id() == head.id() || repo.graph_descendant_of(head.id(), commit.id())? {
+      latest = Some((name.to_string(), version, commit));
+    }
+  }
+
+  Ok(latest)
+}
+
+/// The non-merge commits after `base` up to `head`, oldest first; every commit reachable
+/// from `head` when there is no base.
+fn commits_since<'a>(repo: &'a Repository, base: Option<&Commit<'_>>, head: &Commit<'_>) -> Result<Vec<Commit<'a>>> {
+  let mut revwalk = repo.revwalk()?;
+  revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+  revwalk.push(head.id())?;
+  if let Some(base) = base {
+    revwalk.hide(base.id())?;
+  }
+
+  let mut commits = Vec::new();
+  for oid in revwalk {
+    let commit = repo.find_commit(oid?)?;
+    if commit.parent_count() <= 1 {
+      commits.push(commit);
+    }
+  }
+
+  Ok(commits)
+}
+
+/// Summarizes the commits since the previous release and suggests the next version.
+/// `name` overrides the suggested tag name. The first release is `v0.1.0`.
+pub fn prepare(repo: &Repository, name: Option<&str>) -> Result<Release> {
+  let head = repo
+    .head()
+    .and_then(|head| head.peel_to_commit())
+    .context("Failed to resolve HEAD; there is nothing to tag yet")?;
+  let previous = previous_release(repo, &head)?;
+
+  let commits = commits_since(repo, previous.as_ref().map(|(_, _, commit)| commit), &head)?;
+  if commits.is_empty() {
+    let (tag, ..) = previous.as_ref().context("No commits to release")?;
+    bail!("No commits since {tag}");
+  }
+
+  let mut bump = Bump::Patch;
+  let mut entries = Vec::new();
+  for commit in &commits {
+    let message = commit.message_bytes().to_utf8();
+    let subject = message.lines().next().unwrap_or_default();
+    let files = commit_files(repo, commit)?;
+    bump = bump.max(bump_for(&message, &files));
+    entries.extend(entry_for(subject, &files));
+  }
+
+  // Releases of only chores still get a summary, the commits themselves
+  let mut entries = merge_entries(entries);
+  if entries.is_empty() {
+    entries = commits
+      .iter()
+      .map(|commit| {
+        Entry {
+          section: Section::Changed,
+          scope:   None,
+          text:    commit.summary_bytes().unwrap_or_default().to_utf8()
+        }
+      })
+      .collect();
+  }
+
+  let name = match (name, &previous) {
+    (Some(name), _) => name.to_string(),
+    (None, Some((_, version, _))) => version.bump(bump).to_string(),
+    (None, None) => Version { major: 0, minor: 1, patch: 0, prefix: true }.to_string()
+  };
+  if repo.find_reference(&format!("refs/tags/{name}")).is_ok() {
+    bail!("Tag {name} already exists");
+  }
+
+  Ok(Release {
+    bump: previous
+      .as_ref()
+      .map_or(bump, |(_, version, _)| version.applied(bump)),
+    previous: previous.map(|(tag, ..)| tag),
+    message: format!("{name}\n{}", render_entries(&entries)),
+    name,
+    target: head.id(),
+    commits: commits.len()
+  })
+}
+
+/// Creates the annotated tag for `release`
+pub fn create(repo: &Repository, release: &Release) -> Result<Oid> {
+  let signature = repo.signature().context("Failed to determine the tagger")?;
+  let target = repo.find_object(release.target, None)?;
+  repo
+    .tag(&release.name, &target, &signature, &release.message, false)
+    .with_context(|| format!("Failed to create tag {}", release.name))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_version_parse_and_bump() {
+    let version = Version::parse("v1.2.3").unwrap();
+    assert_eq!(version.bump(Bump::Major).to_string(), "v2.0.0");
+    assert_eq!(version.bump(Bump::Minor).to_string(), "v1.3.0");
+    assert_eq!(version.bump(Bump::Patch).to_string(), "v1.2.4");
+    assert_eq!(
+      Version::parse("0.4.1")
+        .unwrap()
+        .bump(Bump::Major)
+        .to_string(),
+      "0.5.0"
+    );
+    assert_eq!(Version::parse("0.4.1").unwrap().applied(Bump::Major), Bump::Minor);
+    assert!(Version::parse("v1.2.3-rc1").is_none());
+    assert!(Version::parse("v1.2").is_none());
+    assert!(Version::parse("release").is_none());
+  }
+
+  #[test]
+  fn test_previous_release_skips_tags_of_trees() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let signature = git2::Signature::now("Dev", "dev@example.com").unwrap();
+    let tree = repo
+      .find_tree(repo.index().unwrap().write_tree().unwrap())
+      .unwrap();
+    let commit = repo
+      .find_commit(
+        repo
+          .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+          .unwrap()
+      )
+      .unwrap();
+    repo
+      .tag_lightweight("v1.0.0", commit.as_object(), false)
+      .unwrap();
+    repo
+      .tag_lightweight("v2.0.0", tree.as_object(), false)
+      .unwrap();
+
+    let (name, ..) = previous_release(&repo, &commit).unwrap().unwrap();
+    assert_eq!(name, "v1.0.0");
+  }
+
+  #[test]
+  fn test_bump_for_commit_messages() {
+    assert_eq!(bump_for("feat(api)!: drop the v1 endpoints", &[]), Bump::Major);
+    assert_eq!(bump_for("Rework config loading\n\nBREAKING CHANGE: config.ini moved", &[]), Bump::Major);
+    assert_eq!(bump_for("Add config validation", &[]), Bump::Minor);
+    assert_eq!(bump_for("Fix crash in the hook", &[]), Bump::Patch);
+    assert_eq!(bump_for("Update dependencies", &[]), Bump::Patch);
+  }
+}
@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use async_openai::types::chat::CompletionUsage;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+
+use crate::changelog::format_date;
+use crate::config;
+
+/// Built-in prices in USD per million prompt and completion tokens. Entries in
+/// `prices.json` next to the configuration override or extend them.
+const DEFAULT_PRICES: [(&str, Price); 4] = [
+  ("gpt-4.1", Price { prompt: 2.0, completion: 8.0 }),
+  ("gpt-4.1-mini", Price { prompt: 0.4, completion: 1.6 }),
+  ("gpt-4.1-nano", Price { prompt: 0.1, completion: 0.4 }),
+  ("gpt-4.5", Price { prompt: 75.0, completion: 150.0 })
+];
+
+/// One API call as stored in the ledger, a JSON object per line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+  /// Seconds since the epoch
+  pub time:              i64,
+  /// Working directory of the repository the call was made for, empty outside one
+  pub repo:              String,
+  pub model:             String,
+  /// The pipeline step, e.g. `analyze` or `select`
+  pub step:              String,
+  pub prompt_tokens:     u64,
+  pub completion_tokens: u64
+}
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Price {
+  pub prompt:     f64,
+  pub completion: f64
+}
+
+impl Price {
+  pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+    (prompt_tokens as f64 * self.prompt + completion_tokens as f64 * self.completion) / 1_000_000.0
+  }
+}
+
+/// Path of the usage ledger, `~/.config/git-ai/usage.jsonl`
+pub fn ledger_path() -> PathBuf {
+  config::config_dir().join("usage.jsonl")
+}
+
+/// Path of the price table that overrides the built-in prices, `~/.config/git-ai/prices.json`
+pub fn prices_path() -> PathBuf {
+  config::config_dir().join("prices.json")
+}
+
+/// The repository calls are recorded for, resolved from the environment on first use
+static REPO: Mutex<Option<String>> = Mutex::new(None);
+
+fn workdir_name(workdir: &Path) -> String {
+  workdir.components().as_path().display().to_string()
+}
+
+/// Records the following calls for the repository at `workdir`, for processes such as
+/// `git ai serve` that work on other repositories than the current one
+pub fn set_repo(workdir: &Path) {
+  *REPO.lock().unwrap_or_else(|e| e.into_inner()) = Some(workdir_name(workdir));
+}
+
+fn current_repo() -> String {
+  REPO
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .get_or_insert_with(|| {
+      Repository::open_from_env()
+        .ok()
+        .and_then(|repo| repo.workdir().map(workdir_name))
+        .unwrap_or_default()
+    })
+    .clone()
+}
+
+/// Appends the usage of one API response to the ledger. Responses without a `usage`
+/// block are skipped, and failures are only logged so bookkeeping never breaks a run.
+pub fn record(step: &str, model: &str, usage: Option<&CompletionUsage>) {
+  let Some(usage) = usage else {
+    return;
+  };
+
+  let record = Record {
+    time:              SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |elapsed| elapsed.as_secs() as i64),
+    repo:              current_repo(),
+    model:             model.to_string(),
+    step:              step.to_string(),
+    prompt_tokens:     u64::from(usage.prompt_tokens),
+    completion_tokens: u64::from(usage.completion_tokens)
+  };
+
+  if let Err(e) = append(&ledger_path(), &record) {
+    log::debug!("Failed to record usage: {e:#}");
+  }
+}
+
+fn append(path: &Path, record: &Record) -> Result<()> {
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+  let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+  writeln!(file, "{}", serde_json::to_string(record)?)?;
+  Ok(())
+}
+
+/// Reads the ledger at `path`, skipping lines that do not parse. A missing ledger is empty.
+pub fn read_ledger(path: &Path) -> Result<Vec<Record>> {
+  if !path.exists() {
+    return Ok(Vec::new());
+  }
+
+  let file = std::fs::File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+  let mut records = Vec::new();
+  for line in BufReader::new(file).lines() {
+    match serde_json::from_str(&line?) {
+      Ok(record) => records.push(record),
+      Err(e) => log::debug!("Skipping malformed usage record: {e}")
+    }
+  }
+  Ok(records)
+}
+
+/// The built-in prices merged with the table at `path`, if there is one
+pub fn load_prices(path: &Path) -> Result<HashMap<String, Price>> {
+  let mut prices: HashMap<String, Price> = DEFAULT_PRICES
+    .iter()
+    .map(|(model, price)| (model.to_string(), *price))
+    .collect();
+
+  if path.exists() {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let custom: HashMap<String, Price> = serde_json::from_str(&content).with_context(|| {
+      format!(
+        "Failed to parse {}; expected {{\"<model>\": {{\"prompt\": <usd>, \"completion\": <usd>}}}} per million tokens",
+        path.display()
+      )
+    })?;
+    prices.extend(custom);
+  }
+
+  Ok(prices)
+}
+
+/// What to group the report by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+  Day,
+  Repo,
+  Model
+}
+
+impl FromStr for GroupBy {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s.trim().to_lowercase().as_str() {
+      "day" => Ok(GroupBy::Day),
+      "repo" => Ok(GroupBy::Repo),
+      "model" => Ok(GroupBy::Model),
+      _ => bail!("Unknown grouping '{s}'; use day, repo or model")
+    }
+  }
+}
+
+/// Totals for one row of the report
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Totals {
+  pub calls:             usize,
+  pub prompt_tokens:     u64,
+  pub completion_tokens: u64,
+  /// Estimated cost of the calls with a known price
+  pub cost:              f64,
+  /// Calls whose model has no price, so `cost` leaves them out
+  pub unpriced:          usize
+}
+
+impl Totals {
+  fn add(&mut self, record: &Record, price: Option<&Price>) {
+    self.calls += 1;
+    self.prompt_tokens += record.prompt_tokens;
+    self.completion_tokens += record.completion_tokens;
+    match price {
+      Some(price) => self.cost += price.cost(record.prompt_tokens, record.completion_tokens),
+      None => self.unpriced += 1
+    }
+  }
+}
+
+/// A usage report: one row per day, repository or model, in key order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+  pub rows:  Vec<(String, Totals)>,
+  pub total: Totals
+}
+
+/// Sums `records` by `group_by`, pricing each call by its model
+pub fn report(records: &[Record], group_by: GroupBy, prices: &HashMap<String, Price>) -> Report {
+  let mut rows: BTreeMap<String, Totals> = BTreeMap::new();
+  let mut total = Totals::default();
+
+  for record in records {
+    let key = match group_by {
+      GroupBy::Day => format_date(git2::Time::new(record.time, 0)),
+      GroupBy::Repo if record.repo.is_empty() => "(no repository)".to_string(),
+      GroupBy::Repo => record.repo.clone(),
+      GroupBy::Model => record.model.clone()
+    };
+    let price = prices.get(&record.model);
+    rows.entry(key).or_default().add(record, price);
+    total.add(record, price);
+  }
+
+  Report { rows: rows.into_iter().collect(), total }
+}
+
+impl fmt::Display for Report {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let width = self
+      .rows
+      .iter()
+      .map(|(key, _)| key.chars().count())
+      .max()
+      .unwrap_or(0)
+      .max("total".len());
+    let row = |f: &mut fmt::Formatter<'_>, key: &str, totals: &Totals| {
+      let marker = if totals.unpriced > 0 {
+        "*"
+      } else {
+        ""
+      };
+      writeln!(
+        f,
+        "{key:<width$}  {:>6}  {:>12}  {:>12}  {:>10}",
+        totals.calls,
+        totals.prompt_tokens,
+        totals.completion_tokens,
+        format!("${:.4}{marker}", totals.cost)
+      )
+    };
+
+    writeln!(
+      f,
+      "{:<width$}  {:>6}  {:>12}  {:>12}  {:>10}",
+      "", "calls", "prompt", "completion", "cost"
+    )?;
+    for (key, totals) in &self.rows {
+      row(f, key, totals)?;
+    }
+    row(f, "total", &self.total)?;
+    if self.total.unpriced > 0 {
+      writeln!(
+        f,
+        "\n* {} calls used models without a price; add them to prices.json",
+        self.total.unpriced
+      )?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record(time: i64, model: &str, prompt_tokens: u64, completion_tokens: u64) -> Record {
+    Record {
+      time,
+      repo: "/work/app".to_string(),
+      model: model.to_string(),
+      step: "analyze".to_string(),
+      prompt_tokens,
+      completion_tokens
+    }
+  }
+
+  #[test]
+  fn test_report_groups_and_prices_calls() {
+    let prices: HashMap<String, Price> = DEFAULT_PRICES
+      .iter()
+      .map(|(model, price)| (model.to_string(), *price))
+      .collect();
+    let records = vec![record(0, "gpt-4.1-mini", 1_000_000, 0), record(60, "gpt-4.1", 0, 1_000_000), record(86_400, "llama3", 500, 20)];
+
+    let by_day = report(&records, GroupBy::Day, &prices);
+    assert_eq!(by_day.rows.len(), 2);
+    assert_eq!(by_day.rows[0].0, "1970-01-01");
+    assert_eq!(by_day.rows[0].1.calls, 2);
+    assert!((by_day.rows[0].1.cost - 8.4).abs() < 1e-9);
+    assert_eq!(by_day.total.unpriced, 1);
+
+    let by_model = report(&records, GroupBy::Model, &prices);
+    let models: Vec<&str> = by_model.rows.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(models, vec!["gpt-4.1", "gpt-4.1-mini", "llama3"]);
+  }
+
+  #[test]
+  fn test_ledger_round_trip_skips_malformed_lines() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("usage.jsonl");
+    append(&path, &record(1, "gpt-4.1-mini", 10, 2)).unwrap();
+    std::fs::OpenOptions::new()
+      .append(true)
+      .open(&path)
+      .unwrap()
+      .write_all(b"not json\n")
+      .unwrap();
+    append(&path, &record(2, "gpt-4.1", 5, 1)).unwrap();
+
+    let records = read_ledger(&path).unwrap();
+    assert_eq!(records, vec![record(1, "gpt-4.1-mini", 10, 2), record(2, "gpt-4.1", 5, 1)]);
+    assert!(read_ledger(&dir.path().join("missing.jsonl"))
+      .unwrap()
+      .is_empty());
+  }
+}
@@ -95,3 +95,8 @@ impl GitFile {
     Ok(commit)
   }
 }
+
+/// File content with one `{name} {i}` line per number in `lines`
+pub fn numbered(name: &str, lines: std::ops::Range<usize>) -> String {
+  lines.map(|i| format!("{name} {i}\n")).collect()
+}
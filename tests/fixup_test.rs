@@ -0,0 +1,65 @@
+#[allow(dead_code)]
+mod common;
+
+use std::collections::HashSet;
+
+use ai::fixup::*;
+use ai::split::{collect_hunks, staged_diff};
+use common::*;
+
+#[test]
+fn test_attribute_routes_hunks_to_the_commits_they_amend() {
+  let test_repo = TestRepo::default();
+  let repo = &test_repo.repo;
+
+  let first = test_repo
+    .create_file("first.rs", &numbered("first", 0..20))
+    .unwrap();
+  first.stage().unwrap();
+  first.commit().unwrap();
+  let second = test_repo
+    .create_file("second.rs", &numbered("second", 0..20))
+    .unwrap();
+  second.stage().unwrap();
+  second.commit().unwrap();
+
+  let unpushed = unpushed_commits(repo).unwrap();
+  assert_eq!(unpushed.len(), 2);
+  let (first_id, second_id) = (unpushed[0].id(), unpushed[1].id());
+
+  // A changed line in each file, an addition inside the second and a new file
+  test_repo
+    .create_file("first.rs", &numbered("first", 0..20).replace("first 5\n", "first five\n"))
+    .unwrap()
+    .stage()
+    .unwrap();
+  test_repo
+    .create_file(
+      "second.rs",
+      &numbered("second", 0..20)
+        .replace("second 2\n", "second two\n")
+        .replace("second 15\n", "second 15\nsecond 15.5\n")
+    )
+    .unwrap()
+    .stage()
+    .unwrap();
+  test_repo
+    .create_file("third.rs", "third\n")
+    .unwrap()
+    .stage()
+    .unwrap();
+
+  let (_, diff) = staged_diff(repo).unwrap();
+  let hunks = collect_hunks(&diff).unwrap();
+  let lines: Vec<Option<(u32, u32)>> = hunks.iter().map(|hunk| hunk.old_lines).collect();
+  assert_eq!(lines, vec![Some((6, 6)), Some((3, 3)), Some((16, 17)), None]);
+
+  let candidates: HashSet<_> = unpushed.iter().map(|commit| commit.id()).collect();
+  let targets = attribute(repo, &hunks, &candidates).unwrap();
+  assert_eq!(targets, vec![Some(first_id), Some(second_id), Some(second_id), None]);
+
+  // Commits that are already pushed are never amended
+  let pushed: HashSet<_> = [second_id].into_iter().collect();
+  let targets = attribute(repo, &hunks, &pushed).unwrap();
+  assert_eq!(targets, vec![None, Some(second_id), Some(second_id), None]);
+}
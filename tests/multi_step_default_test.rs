@@ -17,7 +17,7 @@ index 1234567..abcdefg 100644
 "#;
 
   // Test that local multi-step generation works
-  let result = generate_commit_message_local(test_diff, Some(72));
+  let result = generate_commit_message_local(test_diff, Some(72), "en");
   assert!(result.is_ok());
 
   let message = result.unwrap();
@@ -74,6 +74,14 @@ fn fallback_template_keeps_max_length_placeholder() {
   );
 }
 
+#[test]
+fn fallback_template_keeps_language_placeholder() {
+  assert!(
+    RAW_PROMPT_MD.contains("{{language}}"),
+    "resources/prompt.md must preserve the {{{{language}}}} mustache placeholder"
+  );
+}
+
 #[test]
 fn fallback_template_keeps_function_and_mood_invariants() {
   let p = lower(RAW_PROMPT_MD);
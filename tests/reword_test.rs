@@ -0,0 +1,90 @@
+#[allow(dead_code)]
+mod common;
+
+use ai::reword::*;
+use common::*;
+
+fn repo_with_commits(count: usize) -> TestRepo {
+  let test_repo = TestRepo::default();
+  for i in 0..count {
+    let file = test_repo
+      .create_file(&format!("file{i}.txt"), &format!("content {i}\n"))
+      .unwrap();
+    file.stage().unwrap();
+    file.commit().unwrap();
+  }
+  test_repo
+}
+
+#[test]
+fn test_commits_in_range_oldest_first() {
+  let test_repo = repo_with_commits(3);
+  let repo = &test_repo.repo;
+  let head = repo.head().unwrap().peel_to_commit().unwrap();
+
+  let commits = commits_in_range(repo, "HEAD~2").unwrap();
+  assert_eq!(commits.len(), 2);
+  assert_eq!(commits[1].id(), head.id());
+  assert_eq!(commits[0].id(), head.parent_id(0).unwrap());
+
+  // The explicit form covers the same commits
+  assert_eq!(commits_in_range(repo, "HEAD~2..HEAD").unwrap().len(), 2);
+}
+
+#[test]
+fn test_commits_in_range_must_end_at_head() {
+  let test_repo = repo_with_commits(3);
+  let error = commits_in_range(&test_repo.repo, "HEAD~2..HEAD~1").unwrap_err();
+  assert!(error.to_string().contains("must end at HEAD"), "{error}");
+}
+
+#[test]
+fn test_pushed_commits_reachable_from_remote() {
+  let test_repo = repo_with_commits(3);
+  let repo = &test_repo.repo;
+  let head = repo.head().unwrap().peel_to_commit().unwrap();
+  let parent = head.parent(0).unwrap();
+  repo
+    .reference("refs/remotes/origin/main", parent.id(), true, "test")
+    .unwrap();
+
+  let commits = commits_in_range(repo, "HEAD~2").unwrap();
+  assert_eq!(pushed_commits(repo, &commits).unwrap(), vec![parent.id()]);
+}
+
+#[test]
+fn test_rewrite_keeps_trees_and_backs_up_old_tip() {
+  let test_repo = repo_with_commits(3);
+  let repo = &test_repo.repo;
+  let old_head = repo.head().unwrap().peel_to_commit().unwrap();
+  let branch = repo.head().unwrap().name().unwrap().to_string();
+
+  let rewordings: Vec<Rewording> = commits_in_range(repo, "HEAD~2")
+    .unwrap()
+    .iter()
+    .enumerate()
+    .map(|(i, commit)| {
+      Rewording {
+        id:          commit.id(),
+        old_message: commit.message().unwrap().to_string(),
+        new_message: format!("Add file {}", i + 1)
+      }
+    })
+    .collect();
+
+  let new_tip = rewrite(repo, &rewordings).unwrap();
+  let new_head = repo.head().unwrap().peel_to_commit().unwrap();
+  assert_eq!(new_head.id(), new_tip);
+  assert_eq!(new_head.message().unwrap(), "Add file 2");
+  assert_eq!(new_head.tree_id(), old_head.tree_id());
+
+  let new_parent = new_head.parent(0).unwrap();
+  assert_eq!(new_parent.message().unwrap(), "Add file 1");
+  assert_eq!(new_parent.tree_id(), old_head.parent(0).unwrap().tree_id());
+  assert_eq!(new_parent.parent_id(0).unwrap(), old_head.parent(0).unwrap().parent_id(0).unwrap());
+
+  let backup = repo
+    .find_reference(&format!("{BACKUP_REF_PREFIX}{branch}"))
+    .unwrap();
+  assert_eq!(backup.target().unwrap(), old_head.id());
+}
@@ -0,0 +1,81 @@
+#[allow(dead_code)]
+mod common;
+
+use ai::split::*;
+use common::*;
+
+#[test]
+fn test_split_reproduces_index_and_separates_groups() {
+  let test_repo = TestRepo::default();
+  let repo = &test_repo.repo;
+
+  std::fs::create_dir_all(test_repo.repo_path.path().join("src")).unwrap();
+  let mut config = repo.config().unwrap();
+  config.set_str("user.name", "Your Name").unwrap();
+  config.set_str("user.email", "email@example.com").unwrap();
+
+  let source = test_repo
+    .create_file("src/lib.rs", &numbered("line", 0..40))
+    .unwrap();
+  source.stage().unwrap();
+  source.commit().unwrap();
+
+  // One code hunk and one comment-only hunk in the same file, plus a new doc file
+  let changed = numbered("line", 0..40)
+    .replace("line 2\n", "line two\n")
+    .replace("line 30\n", "// line 30 explained\nline 30\n");
+  test_repo
+    .create_file("src/lib.rs", &changed)
+    .unwrap()
+    .stage()
+    .unwrap();
+  test_repo
+    .create_file("NOTES.md", "# Notes\n")
+    .unwrap()
+    .stage()
+    .unwrap();
+
+  let (head, diff) = staged_diff(repo).unwrap();
+  let hunks = collect_hunks(&diff).unwrap();
+  assert_eq!(hunks.len(), 3);
+
+  let groups = cluster(&hunks);
+  let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+  assert_eq!(names, vec!["src", "documentation"]);
+
+  let trees = build_trees(repo, head.as_ref(), &diff, &groups).unwrap();
+  assert_eq!(trees.len(), 2);
+  assert_eq!(trees[1], repo.index().unwrap().write_tree().unwrap());
+
+  // The first commit has the code change but not the comment or the notes
+  let first = repo.find_tree(trees[0]).unwrap();
+  assert!(first.get_name("NOTES.md").is_none());
+  let blob = first
+    .get_path(std::path::Path::new("src/lib.rs"))
+    .unwrap()
+    .to_object(repo)
+    .unwrap()
+    .peel_to_blob()
+    .unwrap();
+  let content = std::str::from_utf8(blob.content()).unwrap();
+  assert!(content.contains("line two\n"));
+  assert!(!content.contains("explained"));
+
+  let planned: Vec<PlannedCommit> = groups
+    .into_iter()
+    .zip(trees.iter().copied())
+    .map(|(group, tree)| {
+      PlannedCommit {
+        message: format!("Update {}", group.name),
+        group,
+        tree
+      }
+    })
+    .collect();
+  apply(repo, &planned).unwrap();
+
+  let head = repo.head().unwrap().peel_to_commit().unwrap();
+  assert_eq!(head.message().unwrap(), "Update documentation");
+  assert_eq!(head.tree_id(), trees[1]);
+  assert_eq!(head.parent(0).unwrap().tree_id(), trees[0]);
+}